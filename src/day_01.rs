@@ -1,25 +1,145 @@
 use std::fs;
-use core::slice::Iter;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/**
+   A row of the input that doesn't fit into the table. Lines are numbered from 1, columns from 0.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowProblem {
+    /// The row has fewer values than expected
+    Missing { line: usize, found: usize },
+    /// The row has more values than expected
+    Extra { line: usize, found: usize },
+    Invalid { line: usize, column: usize, value: String },
+}
+
+impl std::fmt::Display for RowProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowProblem::Missing { line, found } => write!(f, "Line {} has only {} values", line, found),
+            RowProblem::Extra { line, found } => write!(f, "Line {} has {} values, which are too many", line, found),
+            RowProblem::Invalid { line, column, value } => write!(f, "Line {} has the invalid value {:?} in column {}", line, value, column),
+        }
+    }
+}
+
+/**
+   The whitespace separated rows of an input. Rows may have different lengths, and every row keeps
+   the values it has. Invalid values are stored as None.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table<T> {
+    /// The line number and the values of every non-empty row
+    pub rows: Vec<(usize, Vec<Option<T>>)>,
+    pub problems: Vec<RowProblem>,
+}
+
+impl<T: std::str::FromStr> Table<T> {
+    /**
+       Parses the contents into rows. With a given width, rows with a different number of values
+       are reported as problems, but are kept. Empty lines are skipped.
+     */
+    pub fn parse(contents: &str, width: Option<usize>) -> Self {
+        let mut rows = vec![];
+        let mut problems = vec![];
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let values: Vec<&str> = line.split_whitespace().collect();
+            if values.is_empty() {
+                continue;
+            }
+
+            match width {
+                Some(width) if values.len() < width => {
+                    problems.push(RowProblem::Missing { line: line_number, found: values.len() });
+                }
+                Some(width) if values.len() > width => {
+                    problems.push(RowProblem::Extra { line: line_number, found: values.len() });
+                }
+                _ => {}
+            }
+
+            let row = values.iter().enumerate()
+                .map(|(column, value)| {
+                    let parsed = value.parse().ok();
+                    if parsed.is_none() {
+                        problems.push(RowProblem::Invalid { line: line_number, column, value: value.to_string() });
+                    }
+                    parsed
+                })
+                .collect();
+            rows.push((line_number, row));
+        }
+
+        Table { rows, problems }
+    }
+
+    pub fn read(path: &str, width: Option<usize>) -> Self {
+        let contents = fs::read_to_string(path).expect(path);
+        Self::parse(&contents, width)
+    }
+}
+
+impl<T: Clone> Table<T> {
+    /**
+       Returns the number of values of the longest row
+     */
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0)
+    }
+
+    /**
+       Returns copies of the two chosen columns, taken from every row that has valid values in
+       both of them, together with the problems of the rows that don't. Returns None if no row
+       reaches one of the columns.
+     */
+    pub fn pair(&self, first: usize, second: usize) -> Option<(Vec<T>, Vec<T>, Vec<RowProblem>)> {
+        if first.max(second) >= self.width() {
+            return None;
+        }
+
+        let mut left = vec![];
+        let mut right = vec![];
+        let mut problems = vec![];
+
+        for (line, row) in &self.rows {
+            if row.len() <= first.max(second) {
+                problems.push(RowProblem::Missing { line: *line, found: row.len() });
+                continue;
+            }
+
+            match (&row[first], &row[second]) {
+                (Some(first_value), Some(second_value)) => {
+                    left.push(first_value.clone());
+                    right.push(second_value.clone());
+                }
+                _ => problems.extend(self.problems.iter()
+                    .filter(|problem| matches!(problem, RowProblem::Invalid { line: problem_line, column, .. }
+                        if problem_line == line && (*column == first || *column == second)))
+                    .cloned()),
+            }
+        }
+
+        Some((left, right, problems))
+    }
+}
 
 /**
  * Returns the pre-processed vectors of the two input columns.
  * The vectors are already sorted from smallest to largest numbers
  */
-fn preprocess() -> (Vec<u32>, Vec<u32>) {
+fn preprocess<T: std::str::FromStr + Ord + Clone>() -> (Vec<T>, Vec<T>) {
     let path = "inputs/day_01.txt";
-    let contents = fs::read_to_string(path).expect(path);
-    let lines_iterator = contents.lines().map(|x| x.to_string());
-
-    let mut list_1: Vec<u32> = vec![];
-    let mut list_2: Vec<u32> = vec![];
-
-    lines_iterator
-        .for_each(|line| {
-            let mut line_iterator = line.split_whitespace();
-            list_1.push(line_iterator.next().unwrap().parse().unwrap());
-            list_2.push(line_iterator.next().unwrap().parse().unwrap());
-        });
+    let table: Table<T> = Table::read(path, Some(2));
+    if let Some(problem) = table.problems.first() {
+        panic!("{}: {}", path, problem);
+    }
 
+    let (mut list_1, mut list_2) = table.pair(0, 1)
+        .map(|(list_1, list_2, _)| (list_1, list_2))
+        .unwrap_or_default();
     list_1.sort();
     list_2.sort();
 
@@ -30,84 +150,351 @@ fn preprocess() -> (Vec<u32>, Vec<u32>) {
    Sums up the differences between the left and right list rows
  */
 pub fn part_1() {
-    let (list_1, list_2) = preprocess();
-
-    let result: u32 = list_1.iter().zip(list_2.iter())
-        .map(|(left, right)| left.abs_diff(*right))
-        .sum();
+    let (list_1, list_2) = preprocess::<u32>();
+    let result = total_distance(&list_1, &list_2).expect("The distance overflowed");
 
     println!("Result: {}", result);
 }
 
 /**
-   Counts the occurrences of the given number *of* and returns the number of
-   occurences and the next number.
-   Expects an iterator over a sorted list. Modifies the given iterator!
+   Counts how often each id occurs in the list. The list doesn't have to be sorted.
  */
-fn count_occurences(iter: &mut Iter<'_, u32>, of: u32) -> (u32, Option<u32>) {
-    let mut count: u32 = 0;
-    let mut next_number: u32;
+pub fn frequencies_of<T: Copy + Eq + Hash>(list: &[T]) -> HashMap<T, u64> {
+    let mut frequencies = HashMap::new();
 
-    loop {
-        let next_number_option = iter.next();
-        if next_number_option.is_none() {
-            return (count, None);
-        }
+    for &id in list {
+        *frequencies.entry(id).or_insert(0) += 1;
+    }
 
-        next_number = *next_number_option.unwrap();
-        
-        if next_number != of {
-            break;
-        }
+    frequencies
+}
 
-        count += 1;
-    }
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Similarity {
+    /// Each id of the left list, multiplied by how often it occurs in the right list. This is
+    /// the score of the puzzle, and it counts every pair of equal ids.
+    LeftWeighted,
+    /// Each id that occurs in both lists, multiplied by how often it occurs in the list where it
+    /// is rarer. This pairs every occurrence at most once.
+    Symmetric,
+}
 
-    (count, Some(next_number))
+/**
+   Computes the similarity score of the two lists, for any kind of integer ids. Returns None if
+   the score doesn't fit into an i128.
+ */
+pub fn similarity<T: Copy + Eq + Hash + Into<i128>>(left: &[T], right: &[T], kind: Similarity) -> Option<i128> {
+    let left_frequencies = frequencies_of(left);
+    let right_frequencies = frequencies_of(right);
+
+    left_frequencies.iter().try_fold(0i128, |score, (&id, &left_count)| {
+        let right_count = *right_frequencies.get(&id).unwrap_or(&0);
+        let count = match kind {
+            Similarity::LeftWeighted => (left_count as i128).checked_mul(right_count as i128)?,
+            Similarity::Symmetric => left_count.min(right_count) as i128,
+        };
+
+        score.checked_add(id.into().checked_mul(count)?)
+    })
 }
 
 /**
-   Implements a dual pointer approach for the second part of the first day.
+   Computes the similarity score of the puzzle with a frequency map of the right list
  */
 pub fn part_2() {
-    let (list_1, list_2) = preprocess();
+    let (list_1, list_2) = preprocess::<u32>();
+    let similarity_score = similarity(&list_1, &list_2, Similarity::LeftWeighted).expect("The similarity score overflowed");
 
-    let mut similarity_score: u32 = 0;
-    let mut iter_1 = list_1.iter();
-    let mut iter_2 = list_2.iter();
+    println!("Result: {}", similarity_score);
+}
 
-    let mut current_right_number = 0;
-    let mut next_right_number_option: Option<u32> = Some(*iter_2.next().unwrap());
-    let mut current_right_sum: u32 = 0;
+/**
+   A row of the two lists, after both were sorted
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AlignedRow<T> {
+    pub left: T,
+    pub right: T,
+    pub difference: u128,
+}
 
-    'outer: while let Some(current_left_number) = iter_1.next() {
-        // If the number is smaller than the next summed up number, we can skip it
-        if *current_left_number < current_right_number {
-            continue;
-        }
-        
-        // If the current number is greater than the current summed up number,
-        // evaluate the new count before the similarity score is calculated.
-        // Skip right numbers until one is found that is greater than the current left.
-        while *current_left_number > current_right_number {
-            // We can break the loop early if there are no more numbers in the right list.
-            // Because then all remaining left numbers will have 0 occurences in the right list.
-            if next_right_number_option.is_none() {
-                break 'outer;
-            }
+/**
+   Pairs the smallest left id with the smallest right id, the second smallest with the second
+   smallest, and so on. If one list is longer, its largest ids are left out.
+ */
+pub fn rank_alignment<T: Copy + Ord + Into<i128>>(left: &[T], right: &[T]) -> Vec<AlignedRow<T>> {
+    let mut left = left.to_vec();
+    let mut right = right.to_vec();
+    left.sort();
+    right.sort();
 
-            current_right_number = next_right_number_option.unwrap();
-            (current_right_sum, next_right_number_option) = count_occurences(&mut iter_2, current_right_number);
-            current_right_sum += 1;
-        }
-        
-        // Similarity score should increase by the occurrences in right list times the current number,
-        // only if the numbers are equal. This might not be the case, because a new right number might
-        // have been picked inside the while loop above
-        if *current_left_number == current_right_number {
-            similarity_score += current_left_number * current_right_sum;
-        }
+    left.into_iter().zip(right)
+        .map(|(left, right)| AlignedRow { left, right, difference: left.into().abs_diff(right.into()) })
+        .collect()
+}
+
+/**
+   The sum of the differences of the aligned rows, or None if it doesn't fit into a u128
+ */
+pub fn total_distance<T: Copy + Ord + Into<i128>>(left: &[T], right: &[T]) -> Option<u128> {
+    rank_alignment(left, right).iter().try_fold(0u128, |sum, row| sum.checked_add(row.difference))
+}
+
+/**
+   The number of distinct ids both lists have in common, divided by the number of distinct ids
+   in any of them. Two empty lists are considered equal.
+ */
+pub fn jaccard_overlap<T: Copy + Eq + Hash>(left: &[T], right: &[T]) -> f64 {
+    let left: HashSet<T> = left.iter().copied().collect();
+    let right: HashSet<T> = right.iter().copied().collect();
+    let union = left.union(&right).count();
+
+    if union == 0 {
+        return 1.0;
     }
+    left.intersection(&right).count() as f64 / union as f64
+}
 
-    println!("Result: {}", similarity_score);
-}
\ No newline at end of file
+/**
+   The distinct ids that only occur in the left list, and the ones that only occur in the right
+   list, both sorted
+ */
+pub fn exclusive_ids<T: Copy + Ord + Hash>(left: &[T], right: &[T]) -> (Vec<T>, Vec<T>) {
+    let left_set: HashSet<T> = left.iter().copied().collect();
+    let right_set: HashSet<T> = right.iter().copied().collect();
+
+    let mut only_left: Vec<T> = left_set.difference(&right_set).copied().collect();
+    let mut only_right: Vec<T> = right_set.difference(&left_set).copied().collect();
+    only_left.sort();
+    only_right.sort();
+
+    (only_left, only_right)
+}
+
+/**
+   The aligned row with the largest difference. The first one wins a tie.
+ */
+pub fn largest_discrepancy<T: Copy + Ord + Into<i128>>(left: &[T], right: &[T]) -> Option<AlignedRow<T>> {
+    rank_alignment(left, right).into_iter().reduce(|largest, row| if row.difference > largest.difference { row } else { largest })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListMetrics<T> {
+    pub distance: Option<u128>,
+    pub similarity: Option<i128>,
+    pub jaccard_overlap: f64,
+    pub only_left: Vec<T>,
+    pub only_right: Vec<T>,
+    pub largest_discrepancy: Option<AlignedRow<T>>,
+}
+
+/**
+   Computes all metrics of the two lists
+ */
+pub fn metrics_of<T: Copy + Ord + Hash + Into<i128>>(left: &[T], right: &[T]) -> ListMetrics<T> {
+    let (only_left, only_right) = exclusive_ids(left, right);
+
+    ListMetrics {
+        distance: total_distance(left, right),
+        similarity: similarity(left, right, Similarity::LeftWeighted),
+        jaccard_overlap: jaccard_overlap(left, right),
+        only_left,
+        only_right,
+        largest_discrepancy: largest_discrepancy(left, right),
+    }
+}
+
+/**
+   Turns the metrics into CSV with a header line and one line per metric. Lists of ids are
+   separated by spaces, missing values are left empty.
+ */
+pub fn metrics_to_csv<T: std::fmt::Display>(metrics: &ListMetrics<T>) -> String {
+    let join = |ids: &[T]| ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(" ");
+    let optional = |value: Option<String>| value.unwrap_or_default();
+
+    let rows = [
+        ("distance", optional(metrics.distance.map(|distance| distance.to_string()))),
+        ("similarity", optional(metrics.similarity.map(|similarity| similarity.to_string()))),
+        ("jaccard_overlap", metrics.jaccard_overlap.to_string()),
+        ("only_left", join(&metrics.only_left)),
+        ("only_right", join(&metrics.only_right)),
+        ("largest_discrepancy", optional(metrics.largest_discrepancy.as_ref().map(|row| row.difference.to_string()))),
+    ];
+
+    let mut csv = "metric,value\n".to_string();
+    for (metric, value) in rows {
+        csv.push_str(&format!("{},{}\n", metric, value));
+    }
+
+    csv
+}
+
+/**
+   Turns the aligned rows into CSV with a header line and one line per row
+ */
+pub fn alignment_to_csv<T: std::fmt::Display>(rows: &[AlignedRow<T>]) -> String {
+    let mut csv = "rank,left,right,difference\n".to_string();
+
+    for (rank, row) in rows.iter().enumerate() {
+        csv.push_str(&format!("{},{},{},{}\n", rank, row.left, row.right, row.difference));
+    }
+
+    csv
+}
+
+/**
+   Reports the metrics of the input, either as a readable summary or as CSV of the aligned rows
+ */
+pub fn report(csv: bool) -> String {
+    let (list_1, list_2) = preprocess::<i64>();
+
+    if csv {
+        return alignment_to_csv(&rank_alignment(&list_1, &list_2));
+    }
+
+    let metrics = metrics_of(&list_1, &list_2);
+    let mut report = String::new();
+    report += &format!("Distance: {:?}\n", metrics.distance);
+    report += &format!("Similarity: {:?}\n", metrics.similarity);
+    report += &format!("Jaccard overlap: {:.4}\n", metrics.jaccard_overlap);
+    report += &format!("Only left: {} ids, only right: {} ids\n", metrics.only_left.len(), metrics.only_right.len());
+    if let Some(row) = metrics.largest_discrepancy {
+        report += &format!("Largest discrepancy: {} between {} and {}\n", row.difference, row.left, row.right);
+    }
+
+    report
+}
+
+/**
+   Computes the metrics of any two columns of the input. Returns the problems of the rows that
+   lack a valid value in one of the two columns as well, because they aren't part of the
+   comparison.
+ */
+pub fn compare_columns(path: &str, first: usize, second: usize) -> (ListMetrics<i64>, Vec<RowProblem>) {
+    let table: Table<i64> = Table::read(path, None);
+    let (left, right, problems) = table.pair(first, second)
+        .unwrap_or_else(|| panic!("{} has only {} columns", path, table.width()));
+
+    (metrics_of(&left, &right), problems)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        let left: Vec<u32> = vec![3, 4, 2, 1, 3, 3];
+        let right: Vec<u32> = vec![4, 3, 5, 3, 9, 3];
+
+        assert_eq!(similarity(&left, &right, Similarity::LeftWeighted), Some(31));
+        assert_eq!(similarity(&right, &left, Similarity::LeftWeighted), Some(31));
+        assert_eq!(similarity(&left, &right, Similarity::Symmetric), Some(3 * 3 + 4));
+        assert_eq!(similarity::<u32>(&[], &right, Similarity::LeftWeighted), Some(0));
+    }
+
+    #[test]
+    fn test_similarity_wide_ids() {
+        let left: Vec<i64> = vec![-5, -5, 7, i64::MAX];
+        let right: Vec<i64> = vec![-5, 7, 7, i64::MAX, i64::MAX];
+        assert_eq!(similarity(&left, &right, Similarity::LeftWeighted), Some(-10 + 14 + 2 * i64::MAX as i128));
+        assert_eq!(similarity(&left, &right, Similarity::Symmetric), Some(-5 + 7 + i64::MAX as i128));
+
+        let big: Vec<u64> = vec![u64::MAX; 3];
+        assert_eq!(similarity(&big, &big, Similarity::LeftWeighted), Some(9 * u64::MAX as i128));
+    }
+
+    #[test]
+    fn test_similarity_overflow() {
+        let left: Vec<i128> = vec![i128::MAX];
+        assert_eq!(similarity(&left, &left, Similarity::LeftWeighted), Some(i128::MAX));
+        assert_eq!(similarity(&left, &[i128::MAX, i128::MAX], Similarity::LeftWeighted), None);
+        assert_eq!(similarity(&[i128::MAX, 1], &left, Similarity::Symmetric), Some(i128::MAX));
+        assert_eq!(similarity(&[i128::MAX, 1], &[i128::MAX, 1], Similarity::Symmetric), None);
+    }
+
+    #[test]
+    fn test_rank_alignment() {
+        let left: Vec<u32> = vec![3, 4, 2, 1, 3, 3];
+        let right: Vec<u32> = vec![4, 3, 5, 3, 9, 3];
+
+        let rows = rank_alignment(&left, &right);
+        assert_eq!(rows[0], AlignedRow { left: 1, right: 3, difference: 2 });
+        assert_eq!(rows.iter().map(|row| row.difference).collect::<Vec<u128>>(), vec![2, 1, 0, 1, 2, 5]);
+        assert_eq!(total_distance(&left, &right), Some(11));
+        assert_eq!(largest_discrepancy(&left, &right), Some(AlignedRow { left: 4, right: 9, difference: 5 }));
+
+        let wide: Vec<i64> = vec![i64::MIN, i64::MAX];
+        assert_eq!(total_distance(&wide, &[i64::MAX, i64::MIN]), Some(0));
+        assert_eq!(total_distance(&wide, &[i64::MAX]), Some(u64::MAX as u128));
+        assert_eq!(largest_discrepancy::<i64>(&[], &[1]), None);
+    }
+
+    #[test]
+    fn test_set_metrics() {
+        let left: Vec<u32> = vec![3, 4, 2, 1, 3, 3];
+        let right: Vec<u32> = vec![4, 3, 5, 3, 9, 3];
+
+        // {3, 4} of {1, 2, 3, 4, 5, 9}
+        assert_eq!(jaccard_overlap(&left, &right), 2.0 / 6.0);
+        assert_eq!(jaccard_overlap::<u32>(&[], &[]), 1.0);
+        assert_eq!(exclusive_ids(&left, &right), (vec![1, 2], vec![5, 9]));
+    }
+
+    #[test]
+    fn test_metrics_to_csv() {
+        let left: Vec<u32> = vec![3, 4, 2, 1, 3, 3];
+        let right: Vec<u32> = vec![4, 3, 5, 3, 9, 3];
+        let metrics = metrics_of(&left, &right);
+
+        assert_eq!(metrics_to_csv(&metrics), concat!(
+            "metric,value\n",
+            "distance,11\n",
+            "similarity,31\n",
+            "jaccard_overlap,0.3333333333333333\n",
+            "only_left,1 2\n",
+            "only_right,5 9\n",
+            "largest_discrepancy,5\n",
+        ));
+        assert!(alignment_to_csv(&rank_alignment(&left, &right)).starts_with("rank,left,right,difference\n0,1,3,2\n1,2,3,1\n"));
+    }
+
+    #[test]
+    fn test_table_parse() {
+        let table: Table<u32> = Table::parse("1 2 3\n4 5\n\n6 7 8 9\n10 x 12\n13 14 15\n", None);
+        let invalid = RowProblem::Invalid { line: 5, column: 1, value: "x".to_string() };
+
+        assert_eq!(table.rows, vec![
+            (1, vec![Some(1), Some(2), Some(3)]),
+            (2, vec![Some(4), Some(5)]),
+            (4, vec![Some(6), Some(7), Some(8), Some(9)]),
+            (5, vec![Some(10), None, Some(12)]),
+            (6, vec![Some(13), Some(14), Some(15)]),
+        ]);
+        assert_eq!(table.problems, vec![invalid.clone()]);
+        assert_eq!(table.width(), 4);
+        assert_eq!(table.pair(0, 1), Some((vec![1, 4, 6, 13], vec![2, 5, 7, 14], vec![invalid])));
+        assert_eq!(table.pair(2, 0), Some((vec![3, 8, 12, 15], vec![1, 6, 10, 13], vec![RowProblem::Missing { line: 2, found: 2 }])));
+        assert_eq!(table.pair(3, 0).map(|(left, right, problems)| (left, right, problems.len())), Some((vec![9], vec![6], 4)));
+        assert_eq!(table.pair(0, 4), None);
+
+        let table: Table<i64> = Table::parse("1 2\n-3 4 5\n", Some(3));
+        assert_eq!(table.rows, vec![(1, vec![Some(1), Some(2)]), (2, vec![Some(-3), Some(4), Some(5)])]);
+        assert_eq!(table.problems, vec![RowProblem::Missing { line: 1, found: 2 }]);
+        assert_eq!(table.problems[0].to_string(), "Line 1 has only 2 values");
+    }
+
+    #[test]
+    fn test_compare_columns() {
+        let (metrics, problems) = compare_columns("./inputs/day_01_test_columns.txt", 0, 2);
+
+        assert_eq!(metrics.distance, Some(13));
+        assert_eq!(metrics.similarity, Some(40));
+        assert_eq!(problems, vec![RowProblem::Missing { line: 4, found: 2 }]);
+
+        let (metrics, problems) = compare_columns("./inputs/day_01_test_columns.txt", 0, 1);
+        assert_eq!(metrics.distance, Some(1));
+        assert_eq!(metrics.only_left, vec![]);
+        assert!(problems.is_empty());
+    }
+}
@@ -1,34 +1,166 @@
+use std::collections::BTreeMap;
+
+/**
+   A single level of a report. Levels may be negative and larger than the puzzle input's.
+ */
+pub type Level = i64;
+
 /**
- * Returns a vector of lines. Each line itself is a vector of u8 integers.
+ * Returns a vector of lines. Each line itself is a vector of levels.
  */
-fn preprocess(path: &str) -> Vec<Vec<u8>> {
+fn preprocess(path: &str) -> Vec<Vec<Level>> {
     let contents = std::fs::read_to_string(path).expect(path);
     let lines_iterator = contents.lines().map(|x| x.to_string());
 
-    let lines: Vec<Vec<u8>> = lines_iterator
+    let lines: Vec<Vec<Level>> = lines_iterator
         .map(|line| line.split_whitespace()
-            .map(|v| v.parse().expect("Number should be u8!"))
+            .map(|v| v.parse().expect("Number should be an integer!"))
             .collect())
         .collect();
 
     lines
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+    Increasing,
+    Decreasing,
+    /// Increasing or decreasing, as long as the whole report agrees
+    Either,
+}
+
 /**
-   Verifies that the two line rules apply to the given line:
-   - Each element in the line increases or decreases
-   - The difference between two consecutive elements must be less or equal to 3
+   The rules a report has to follow to be safe. The default rules are the ones of the puzzle.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SafetyRules {
+    /// The smallest allowed difference between two consecutive levels that aren't equal
+    pub min_step: u64,
+    pub max_step: u64,
+    pub direction: Direction,
+    /// Whether consecutive levels may be equal. Equal levels don't decide the direction.
+    pub allow_equal: bool,
+    /// The minimum number of levels a report needs
+    pub min_length: usize,
+}
+
+impl Default for SafetyRules {
+    fn default() -> Self {
+        SafetyRules { min_step: 1, max_step: 3, direction: Direction::Either, allow_equal: false, min_length: 1 }
+    }
+}
+
+impl SafetyRules {
+    fn assert_valid(&self) {
+        assert!(self.min_step <= self.max_step, "The step range {:?} is empty", self.min_step..=self.max_step);
+    }
+
+    /**
+       The directions a report may have, true meaning increasing
+     */
+    fn directions(&self) -> &'static [bool] {
+        match self.direction {
+            Direction::Increasing => &[true],
+            Direction::Decreasing => &[false],
+            Direction::Either => &[true, false],
+        }
+    }
+
+    /**
+       Whether `to` may follow `from` in a report with the given direction
+     */
+    fn allows_step(&self, from: Level, to: Level, increasing: bool) -> bool {
+        if from == to {
+            return self.allow_equal;
+        }
+
+        let step = from.abs_diff(to);
+        (to > from) == increasing && self.min_step <= step && step <= self.max_step
+    }
+}
+
+/**
+   Why a report is unsafe. The indices are the ones of the later level of the offending pair.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Violation {
+    TooShort,
+    Equal(usize),
+    /// The level goes against the direction the rules require
+    WrongDirection(usize),
+    /// The level goes against the direction the report started with
+    DirectionChange(usize),
+    StepTooSmall(usize),
+    StepTooLarge(usize),
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::TooShort => write!(f, "Too few levels"),
+            Violation::Equal(i) => write!(f, "Equal levels at {}", i),
+            Violation::WrongDirection(i) => write!(f, "Wrong direction at {}", i),
+            Violation::DirectionChange(i) => write!(f, "Direction change at {}", i),
+            Violation::StepTooSmall(i) => write!(f, "Step too small at {}", i),
+            Violation::StepTooLarge(i) => write!(f, "Step too large at {}", i),
+        }
+    }
+}
+
+/**
+   Checks the line against the rules, and returns the first violation if it is unsafe
  */
-fn line_is_safe(line: Vec<u8>) -> bool {
-    let mut safe = true;
-    let is_increasing = line[0] < line[1];
+pub fn check(line: &[Level], rules: &SafetyRules) -> Result<(), Violation> {
+    rules.assert_valid();
+    if line.len() < rules.min_length {
+        return Err(Violation::TooShort);
+    }
+
+    let mut increasing = match rules.direction {
+        Direction::Increasing => Some(true),
+        Direction::Decreasing => Some(false),
+        Direction::Either => None,
+    };
+
+    for i in 1..line.len() {
+        let (from, to) = (line[i - 1], line[i]);
+        if from == to {
+            if !rules.allow_equal {
+                return Err(Violation::Equal(i));
+            }
+            continue;
+        }
 
-    for i in 0..(line.len() - 1) {
-        safe = safe && line[i].abs_diff(line[i+1]) <= 3;
-        safe = safe && if is_increasing { line[i] < line[i+1] } else { line[i] > line[i+1] };
+        match increasing {
+            None => increasing = Some(to > from),
+            Some(direction) if direction != (to > from) => {
+                return Err(match rules.direction {
+                    Direction::Either => Violation::DirectionChange(i),
+                    _ => Violation::WrongDirection(i),
+                });
+            },
+            Some(_) => {},
+        }
+
+        let step = from.abs_diff(to);
+        if step < rules.min_step {
+            return Err(Violation::StepTooSmall(i));
+        }
+        if step > rules.max_step {
+            return Err(Violation::StepTooLarge(i));
+        }
     }
 
-    safe
+    Ok(())
+}
+
+/**
+   Verifies that the two line rules apply to the given line:
+   - Each element in the line increases or decreases
+   - The difference between two consecutive elements must be less or equal to 3
+ */
+fn line_is_safe(line: Vec<Level>) -> bool {
+    check(&line, &SafetyRules::default()).is_ok()
 }
 
 pub fn part_1(path: &str) -> u16 {
@@ -42,25 +174,124 @@ pub fn part_1(path: &str) -> u16 {
 }
 
 /**
-   Brute force approach by removing one element at a time and
-   then checking if the line would be safe without it.
+   The outcome of applying the Problem Dampener to a line
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Dampened {
+    /// The line is safe without removing anything
+    Safe,
+    /// The line becomes safe by removing the level at this index
+    Remove(usize),
+    Unsafe,
+}
+
+/**
+   Finds the first pair of consecutive levels that break the rules for the given direction,
+   while ignoring the level at `skip`. Returns the indices of both levels.
  */
-fn line_is_safe_with_dampener(line: Vec<u8>) -> bool {
-    let line_is_already_safe = line_is_safe(line.clone());
+fn first_violation(line: &[Level], skip: Option<usize>, increasing: bool, rules: &SafetyRules) -> Option<(usize, usize)> {
+    let mut previous: Option<usize> = None;
 
-    if line_is_already_safe {
-        return true;
+    for i in (0..line.len()).filter(|&i| Some(i) != skip) {
+        if let Some(p) = previous {
+            if !rules.allows_step(line[p], line[i], increasing) {
+                return Some((p, i));
+            }
+        }
+        previous = Some(i);
     }
 
-    for i in 0..line.len() {
-        let mut line_copy = line.clone();
-        line_copy.remove(i);
-        if line_is_safe(line_copy) {
-            return true;
-        };
+    None
+}
+
+/**
+   Decides in linear time whether removing at most one level makes the line safe.
+
+   If a single removal fixes the line for a direction, it has to remove one of the two levels of
+   the first violation, because any other removal leaves that pair next to each other. So there
+   are only two candidates per direction to check.
+ */
+pub fn dampen(line: &[Level], rules: &SafetyRules) -> Dampened {
+    rules.assert_valid();
+    if line.len() < rules.min_length {
+        return Dampened::Unsafe;
+    }
+
+    let violations: Vec<(bool, Option<(usize, usize)>)> = rules.directions().iter()
+        .map(|&increasing| (increasing, first_violation(line, None, increasing, rules)))
+        .collect();
+
+    if violations.iter().any(|(_, violation)| violation.is_none()) {
+        return Dampened::Safe;
+    }
+    if line.len() == rules.min_length {
+        return Dampened::Unsafe;
+    }
+
+    for (increasing, violation) in violations {
+        let (first, second) = violation.expect("Checked above");
+        for candidate in [first, second] {
+            if first_violation(line, Some(candidate), increasing, rules).is_none() {
+                return Dampened::Remove(candidate);
+            }
+        }
     }
 
-    false
+    Dampened::Unsafe
+}
+
+fn line_is_safe_with_dampener(line: Vec<Level>) -> bool {
+    dampen(&line, &SafetyRules::default()) != Dampened::Unsafe
+}
+
+/**
+   The minimum number of levels that have to be removed to make the line safe, or None if the
+   line can't become safe because it would be too short.
+
+   For each direction, the longest subsequence is computed in which consecutive levels follow the
+   rules. The longest one ending at level i extends the longest one ending at any earlier level
+   that may come right before i. All other levels have to be removed.
+ */
+pub fn removals_needed(line: &[Level], rules: &SafetyRules) -> Option<usize> {
+    rules.assert_valid();
+
+    let longest_subsequence = |increasing: bool| {
+        let mut longest: Vec<usize> = vec![1; line.len()];
+
+        for i in 0..line.len() {
+            for j in 0..i {
+                if rules.allows_step(line[j], line[i], increasing) {
+                    longest[i] = longest[i].max(longest[j] + 1);
+                }
+            }
+        }
+
+        longest.into_iter().max().unwrap_or(0)
+    };
+
+    let longest = rules.directions().iter().map(|&increasing| longest_subsequence(increasing)).max().unwrap_or(0);
+    (longest >= rules.min_length).then_some(line.len() - longest)
+}
+
+/**
+   Whether removing at most `tolerance` levels makes the line safe
+ */
+pub fn line_is_safe_with_tolerance(line: &[Level], tolerance: usize, rules: &SafetyRules) -> bool {
+    removals_needed(line, rules).is_some_and(|removals| removals <= tolerance)
+}
+
+/**
+   Counts the lines of the input by the number of removals they need to become safe. Lines that
+   can't become safe are counted under None.
+ */
+pub fn removals_histogram(path: &str, rules: &SafetyRules) -> BTreeMap<Option<usize>, usize> {
+    let mut histogram = BTreeMap::new();
+
+    for line in preprocess(path) {
+        *histogram.entry(removals_needed(&line, rules)).or_insert(0) += 1;
+    }
+
+    histogram
 }
 
 pub fn part_2(path: &str) -> u16 {
@@ -77,6 +308,28 @@ pub fn part_2(path: &str) -> u16 {
 mod test {
     use super::*;
 
+    /**
+       Brute force approach by removing one element at a time and
+       then checking if the line would be safe without it.
+     */
+    fn line_is_safe_with_dampener_brute_force(line: Vec<Level>) -> bool {
+        let line_is_already_safe = line_is_safe(line.clone());
+
+        if line_is_already_safe {
+            return true;
+        }
+
+        for i in 0..line.len() {
+            let mut line_copy = line.clone();
+            line_copy.remove(i);
+            if line_is_safe(line_copy) {
+                return true;
+            };
+        }
+
+        false
+    }
+
     #[test]
     fn test_line_is_safe() {
         assert!(line_is_safe(vec![1, 2, 3, 4, 5]));
@@ -119,4 +372,152 @@ mod test {
         let result = part_2("./inputs/day_02_test.txt");
         assert_eq!(result, 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_dampen() {
+        let rules = SafetyRules::default();
+        assert_eq!(dampen(&[7, 6, 4, 2, 1], &rules), Dampened::Safe);
+        assert_eq!(dampen(&[1, 3, 2, 4, 5], &rules), Dampened::Remove(1));
+        assert_eq!(dampen(&[8, 6, 4, 4, 1], &rules), Dampened::Remove(2));
+        assert_eq!(dampen(&[9, 1, 7, 6, 5], &rules), Dampened::Remove(1));
+        assert_eq!(dampen(&[1, 2, 7, 8, 9], &rules), Dampened::Unsafe);
+        // Removing the first level fixes the direction
+        assert_eq!(dampen(&[5, 1, 2, 3, 4], &rules), Dampened::Remove(0));
+        assert_eq!(dampen(&[1, 2, 3, 4, 9], &rules), Dampened::Remove(4));
+    }
+
+    /**
+       Generates lines with small steps in both directions. Uses a xorshift generator, so that
+       failures can be reproduced.
+     */
+    fn random_lines(count: usize) -> Vec<Vec<Level>> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        (0..count).map(|_| {
+            let length = 1 + random(8) as usize;
+            let mut line = vec![random(20) as Level - 10];
+            for _ in 1..length {
+                let step = random(9) as Level - 4;
+                line.push(line.last().unwrap() + step);
+            }
+            line
+        }).collect()
+    }
+
+    #[test]
+    fn test_dampen_random() {
+        let rules = SafetyRules::default();
+        for line in random_lines(20_000) {
+            let expected = line_is_safe_with_dampener_brute_force(line.clone());
+            match dampen(&line, &rules) {
+                Dampened::Safe => assert!(line_is_safe(line.clone()), "{:?}", line),
+                Dampened::Remove(index) => {
+                    assert!(!line_is_safe(line.clone()), "{:?}", line);
+                    let mut removed = line.clone();
+                    removed.remove(index);
+                    assert!(line_is_safe(removed), "{:?} without {}", line, index);
+                },
+                Dampened::Unsafe => assert!(!expected, "{:?}", line),
+            }
+            assert_eq!(line_is_safe_with_dampener(line.clone()), expected, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_removals_needed() {
+        let rules = SafetyRules::default();
+        assert_eq!(removals_needed(&[7, 6, 4, 2, 1], &rules), Some(0));
+        assert_eq!(removals_needed(&[1, 3, 2, 4, 5], &rules), Some(1));
+        assert_eq!(removals_needed(&[1, 2, 7, 8, 9], &rules), Some(2));
+        assert_eq!(removals_needed(&[9, 7, 6, 2, 1], &rules), Some(2));
+        assert_eq!(removals_needed(&[1, 9, 2, 8, 3, 7, 4], &rules), Some(3));
+        assert_eq!(removals_needed(&[5, 5, 5, 5], &rules), Some(3));
+        assert_eq!(removals_needed(&[], &rules), None);
+        assert_eq!(removals_needed(&[4], &rules), Some(0));
+
+        assert!(line_is_safe_with_tolerance(&[1, 2, 7, 8, 9], 2, &rules));
+        assert!(!line_is_safe_with_tolerance(&[1, 2, 7, 8, 9], 1, &rules));
+    }
+
+    #[test]
+    fn test_removals_needed_random() {
+        let rules = SafetyRules::default();
+        for line in random_lines(5_000) {
+            assert_eq!(removals_needed(&line, &rules) == Some(0), line_is_safe(line.clone()), "{:?}", line);
+            assert_eq!(removals_needed(&line, &rules) <= Some(1), line_is_safe_with_dampener_brute_force(line.clone()), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_removals_histogram() {
+        let rules = SafetyRules::default();
+        let histogram = removals_histogram("./inputs/day_02_test.txt", &rules);
+        assert_eq!(histogram, BTreeMap::from([(Some(0), 2), (Some(1), 2), (Some(2), 2)]));
+    }
+
+    #[test]
+    fn test_check() {
+        let rules = SafetyRules::default();
+        assert_eq!(check(&[1, 2, 3], &rules), Ok(()));
+        assert_eq!(check(&[1, 2, 1], &rules), Err(Violation::DirectionChange(2)));
+        assert_eq!(check(&[1, 2, 6], &rules), Err(Violation::StepTooLarge(2)));
+        assert_eq!(check(&[8, 6, 4, 4, 1], &rules), Err(Violation::Equal(3)));
+        assert_eq!(check(&[], &rules), Err(Violation::TooShort));
+        assert_eq!(check(&[7], &rules), Ok(()));
+        assert_eq!(check(&[-1_000_000_000_000, -999_999_999_998], &rules), Ok(()));
+        assert!(line_is_safe(vec![5]));
+
+        let rules = SafetyRules { min_step: 2, max_step: 5, direction: Direction::Decreasing, allow_equal: true, min_length: 3 };
+        assert_eq!(check(&[9, 9, 4, 2, 2], &rules), Ok(()));
+        assert_eq!(check(&[9, 8], &rules), Err(Violation::TooShort));
+        assert_eq!(check(&[9, 9, 10], &rules), Err(Violation::WrongDirection(2)));
+        assert_eq!(check(&[9, 8, 7], &rules), Err(Violation::StepTooSmall(1)));
+        assert_eq!(Violation::StepTooSmall(1).to_string(), "Step too small at 1");
+    }
+
+    /**
+       The minimum number of removals, by trying every subset of the levels
+     */
+    fn removals_needed_brute_force(line: &[Level], rules: &SafetyRules) -> Option<usize> {
+        (0..1u32 << line.len())
+            .filter_map(|kept| {
+                let subsequence: Vec<Level> = (0..line.len()).filter(|i| kept & (1 << i) != 0).map(|i| line[i]).collect();
+                check(&subsequence, rules).ok().map(|_| line.len() - subsequence.len())
+            })
+            .min()
+    }
+
+    #[test]
+    fn test_custom_rules_random() {
+        let all_rules = [
+            SafetyRules::default(),
+            SafetyRules { allow_equal: true, ..SafetyRules::default() },
+            SafetyRules { min_step: 2, max_step: 4, direction: Direction::Increasing, ..SafetyRules::default() },
+            SafetyRules { min_step: 0, max_step: 1, direction: Direction::Decreasing, allow_equal: true, min_length: 3 },
+        ];
+
+        for rules in &all_rules {
+            for line in random_lines(2_000) {
+                let expected = removals_needed_brute_force(&line, rules);
+                assert_eq!(removals_needed(&line, rules), expected, "{:?} {:?}", line, rules);
+
+                match dampen(&line, rules) {
+                    Dampened::Safe => assert_eq!(expected, Some(0), "{:?} {:?}", line, rules),
+                    Dampened::Remove(index) => {
+                        let mut removed = line.clone();
+                        removed.remove(index);
+                        assert_eq!(expected, Some(1), "{:?} {:?}", line, rules);
+                        assert_eq!(check(&removed, rules), Ok(()), "{:?} without {}", line, index);
+                    },
+                    Dampened::Unsafe => assert!(expected.is_none_or(|removals| removals > 1), "{:?} {:?}", line, rules),
+                }
+            }
+        }
+    }
+}
@@ -1,51 +1,596 @@
-use regex::Regex;
+use std::io::Read;
 
 /**
-   Returns the line containing the instructions from the input file
+   The instructions of the corrupted memory language
  */
-fn preprocess(path: &str) -> String {
-    std::fs::read_to_string(path).expect(path)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Instruction {
+    /// `mul(a,b)` adds the product of the two numbers to the accumulator
+    Mul(i64, i64),
+    /// `do()` enables the following `mul` instructions
+    Do,
+    /// `don't()` disables the following `mul` instructions
+    Dont,
 }
 
-pub fn part_1(path: &str) -> u32 {
-    let input = preprocess(path);
-    let expression = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
+/**
+   A recognized instruction, together with the position of its text in the input
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Token {
+    pub instruction: Instruction,
+    /// The byte offset of the first character of the instruction
+    pub offset: usize,
+    /// The length of the instruction text in bytes
+    pub length: usize,
+}
 
-    let mut sum: u32 = 0;
-    for (_, [number_1, number_2]) in expression.captures_iter(input.as_str()).map(|c| c.extract()) {
-        sum += number_1.parse::<u32>().expect("Should be a parsable u32")
-            * number_2.parse::<u32>().expect("Should be a parsable u32");
+/**
+   The syntax the lexer accepts for `mul` instructions. The default is the syntax of the puzzle.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Syntax {
+    /// The maximum number of digits of each operand, at most 18 so that an operand fits an i64
+    pub max_digits: usize,
+    /// The maximum number of whitespace characters between any two parts of a `mul` instruction
+    pub max_whitespace: usize,
+    /// Whether operands may start with a `+` or `-` sign
+    pub signed: bool,
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax { max_digits: 3, max_whitespace: 0, signed: false }
     }
+}
 
-    println!("Result: {}", sum);
-    sum
+impl Syntax {
+    fn assert_valid(&self) {
+        assert!((1..=18).contains(&self.max_digits), "Operands must have between 1 and 18 digits, not {}", self.max_digits);
+    }
+
+    /**
+       The longest text any instruction can have. A decision about an instruction, or a near miss
+       of one, can be made as soon as this many bytes are available.
+     */
+    fn max_instruction_length(&self) -> usize {
+        let operand = self.max_digits + self.signed as usize;
+        let mul = "mul(,)".len() + 2 * operand + 5 * self.max_whitespace;
+        mul.max("don't()".len())
+    }
+}
+
+/**
+   Why a text that starts like a `mul` instruction isn't one
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MissReason {
+    /// More whitespace than the syntax allows
+    Whitespace,
+    /// A sign, while the syntax doesn't allow them
+    Sign,
+    TooManyDigits,
+    MissingOperand,
+    Unexpected(u8),
+    UnexpectedEnd,
+}
+
+impl std::fmt::Display for MissReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissReason::Whitespace => write!(f, "Too much whitespace"),
+            MissReason::Sign => write!(f, "Signed operand"),
+            MissReason::TooManyDigits => write!(f, "Operand with too many digits"),
+            MissReason::MissingOperand => write!(f, "Missing operand"),
+            MissReason::Unexpected(byte) if byte.is_ascii_graphic() => write!(f, "Unexpected {:?}", *byte as char),
+            MissReason::Unexpected(byte) => write!(f, "Unexpected '\\x{:02X}'", byte),
+            MissReason::UnexpectedEnd => write!(f, "Unexpected end of input"),
+        }
+    }
+}
+
+/**
+   A text that starts like a `mul` instruction with `mul(`, but isn't a valid one
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NearMiss {
+    pub offset: usize,
+    /// The length of the text up to and including the first byte that doesn't fit
+    pub length: usize,
+    pub reason: MissReason,
+}
+
+/**
+   Everything the lexer finds in the corrupted memory
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Lexeme {
+    Token(Token),
+    NearMiss(NearMiss),
+}
+
+/**
+   The result of trying to read an instruction from the start of the input. Either the
+   instruction and the number of bytes it takes up, or the reason it failed and the number of
+   bytes that were looked at.
+ */
+type Parsed = Result<(Instruction, usize), (MissReason, usize)>;
+
+/**
+   Tries to read an instruction from the start of the input. Returns None if the input doesn't
+   start with this instruction at all.
+ */
+type InstructionParser = fn(&[u8], &Syntax) -> Option<Parsed>;
+
+/**
+   All instructions the lexer knows. Adding a new instruction only needs a new parser in this list.
+ */
+const INSTRUCTION_PARSERS: [InstructionParser; 3] = [parse_mul, parse_do, parse_dont];
+
+/**
+   Reads the parts of a `mul` instruction one after another, and stops at the first violation
+   of the syntax
+ */
+struct MulReader<'a> {
+    input: &'a [u8],
+    syntax: &'a Syntax,
+    position: usize,
+}
+
+impl MulReader<'_> {
+    fn fail(&self, reason: MissReason) -> (MissReason, usize) {
+        (reason, (self.position + 1).min(self.input.len()))
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), (MissReason, usize)> {
+        let mut whitespace = 0;
+        while self.input.get(self.position).is_some_and(|c| c.is_ascii_whitespace()) {
+            if whitespace == self.syntax.max_whitespace {
+                return Err(self.fail(MissReason::Whitespace));
+            }
+            whitespace += 1;
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), (MissReason, usize)> {
+        match self.input.get(self.position) {
+            Some(&c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            },
+            Some(&c) => Err(self.fail(MissReason::Unexpected(c))),
+            None => Err(self.fail(MissReason::UnexpectedEnd)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<i64, (MissReason, usize)> {
+        let negative = match self.input.get(self.position) {
+            Some(b'+' | b'-') if !self.syntax.signed => return Err(self.fail(MissReason::Sign)),
+            Some(&sign @ (b'+' | b'-')) => {
+                self.position += 1;
+                sign == b'-'
+            },
+            _ => false,
+        };
+
+        let mut number: i64 = 0;
+        let mut digits = 0;
+        while let Some(&digit) = self.input.get(self.position).filter(|c| c.is_ascii_digit()) {
+            if digits == self.syntax.max_digits {
+                return Err(self.fail(MissReason::TooManyDigits));
+            }
+            number = number * 10 + (digit - b'0') as i64;
+            digits += 1;
+            self.position += 1;
+        }
+
+        match self.input.get(self.position) {
+            _ if digits > 0 => Ok(if negative { -number } else { number }),
+            Some(_) => Err(self.fail(MissReason::MissingOperand)),
+            None => Err(self.fail(MissReason::UnexpectedEnd)),
+        }
+    }
+
+    fn read(&mut self) -> Parsed {
+        self.skip_whitespace()?;
+        let first = self.operand()?;
+        self.skip_whitespace()?;
+        self.expect(b',')?;
+        self.skip_whitespace()?;
+        let second = self.operand()?;
+        self.skip_whitespace()?;
+        self.expect(b')')?;
+
+        Ok((Instruction::Mul(first, second), self.position))
+    }
+}
+
+/**
+   Parses `mul(a,b)`, with operands and whitespace as allowed by the syntax. Everything starting
+   with `mul(` is either an instruction or a near miss, `mul` may be separated from the
+   parenthesis by any whitespace for this.
+ */
+fn parse_mul(input: &[u8], syntax: &Syntax) -> Option<Parsed> {
+    if !input.starts_with(b"mul") {
+        return None;
+    }
+
+    let whitespace = input[3..].iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let position = 3 + whitespace;
+    if input.get(position) != Some(&b'(') {
+        return None;
+    }
+    if whitespace > syntax.max_whitespace {
+        return Some(Err((MissReason::Whitespace, 3 + syntax.max_whitespace + 1)));
+    }
+
+    Some(MulReader { input, syntax, position: position + 1 }.read())
+}
+
+fn parse_do(input: &[u8], _: &Syntax) -> Option<Parsed> {
+    input.starts_with(b"do()").then_some(Ok((Instruction::Do, "do()".len())))
+}
+
+fn parse_dont(input: &[u8], _: &Syntax) -> Option<Parsed> {
+    input.starts_with(b"don't()").then_some(Ok((Instruction::Dont, "don't()".len())))
+}
+
+/**
+   The number of bytes that are read from the input at once by default
+ */
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/**
+   Scans corrupted memory from any reader for instructions and near misses of them, without ever
+   holding more than one chunk of the input in memory. Everything else is skipped. Scanning
+   continues behind each found instruction, so instructions never overlap. Near misses may
+   contain instructions though, like `mul(mul(1,2)`.
+
+   An instruction may be split across two chunks. Therefore a position is only scanned once the
+   longest possible instruction behind it is buffered, or the input has ended.
+ */
+pub struct TokenStream<R: Read> {
+    reader: R,
+    syntax: Syntax,
+    chunk_size: usize,
+    /// The bytes that were read, but not scanned yet
+    buffer: Vec<u8>,
+    /// The offset of the first buffered byte in the input
+    buffer_offset: usize,
+    /// The position inside of the buffer that is scanned next
+    position: usize,
+    end_of_input: bool,
+}
+
+impl<R: Read> TokenStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_syntax(reader, Syntax::default(), DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(reader: R, chunk_size: usize) -> Self {
+        Self::with_syntax(reader, Syntax::default(), chunk_size)
+    }
+
+    pub fn with_syntax(reader: R, syntax: Syntax, chunk_size: usize) -> Self {
+        syntax.assert_valid();
+        assert!(chunk_size > 0, "The chunk size must be at least 1");
+        TokenStream { reader, syntax, chunk_size, buffer: vec![], buffer_offset: 0, position: 0, end_of_input: false }
+    }
+
+    /**
+       Drops the scanned bytes and reads the next chunk into the buffer
+     */
+    fn fill_buffer(&mut self) -> std::io::Result<()> {
+        self.buffer.drain(..self.position);
+        self.buffer_offset += self.position;
+        self.position = 0;
+
+        let buffered = self.buffer.len();
+        self.buffer.resize(buffered + self.chunk_size, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[buffered..]) {
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        let read = read.inspect_err(|_| self.buffer.truncate(buffered))?;
+
+        self.buffer.truncate(buffered + read);
+        self.end_of_input = read == 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for TokenStream<R> {
+    type Item = std::io::Result<Lexeme>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window = self.syntax.max_instruction_length();
+
+        loop {
+            // Make sure that an instruction starting at the position is completely buffered
+            while !self.end_of_input && self.buffer.len() - self.position < window {
+                if let Err(error) = self.fill_buffer() {
+                    return Some(Err(error));
+                }
+            }
+
+            if self.position >= self.buffer.len() {
+                return None;
+            }
+
+            // Only look at the window, so that the result doesn't depend on the chunk size
+            let end = self.buffer.len().min(self.position + window);
+            let remaining = &self.buffer[self.position..end];
+            let parsed = INSTRUCTION_PARSERS.iter().find_map(|parser| parser(remaining, &self.syntax));
+            let offset = self.buffer_offset + self.position;
+
+            match parsed {
+                Some(Ok((instruction, length))) => {
+                    self.position += length;
+                    return Some(Ok(Lexeme::Token(Token { instruction, offset, length })));
+                },
+                Some(Err((reason, length))) => {
+                    self.position += 1;
+                    return Some(Ok(Lexeme::NearMiss(NearMiss { offset, length, reason })));
+                },
+                None => self.position += 1,
+            }
+        }
+    }
+}
+
+/**
+   Scans the corrupted memory for instructions and near misses, see `TokenStream`
+ */
+pub fn lex(input: &[u8], syntax: Syntax) -> Vec<Lexeme> {
+    TokenStream::with_syntax(input, syntax, DEFAULT_CHUNK_SIZE)
+        .collect::<std::io::Result<Vec<Lexeme>>>()
+        .expect("Reading from memory can't fail")
+}
+
+/**
+   Scans the corrupted memory for instructions with the syntax of the puzzle
+ */
+pub fn tokenize(input: &str) -> Vec<Token> {
+    lex(input.as_bytes(), Syntax::default()).into_iter()
+        .filter_map(|lexeme| match lexeme {
+            Lexeme::Token(token) => Some(token),
+            Lexeme::NearMiss(_) => None,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Io(std::io::Error),
+    /// The accumulator overflowed at the instruction with the offset
+    Overflow(usize),
+}
+
+impl From<std::io::Error> for RunError {
+    fn from(error: std::io::Error) -> Self {
+        RunError::Io(error)
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Io(error) => write!(f, "Reading the input failed: {}", error),
+            RunError::Overflow(offset) => write!(f, "The accumulator overflowed at offset {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RunReport {
+    pub sum: i128,
+    pub near_misses: usize,
+}
+
+/**
+   Executes all instructions of the reader on a new machine. Returns the final accumulator and
+   the number of near misses.
+ */
+pub fn run_stream<R: Read>(reader: R, syntax: Syntax, conditionals: bool) -> Result<RunReport, RunError> {
+    let mut machine = Machine::new(conditionals);
+    let mut near_misses = 0;
+
+    for lexeme in TokenStream::with_syntax(reader, syntax, DEFAULT_CHUNK_SIZE) {
+        match lexeme? {
+            Lexeme::Token(token) => {
+                machine.execute(token.instruction).ok_or(RunError::Overflow(token.offset))?;
+            },
+            Lexeme::NearMiss(_) => near_misses += 1,
+        }
+    }
+
+    Ok(RunReport { sum: machine.accumulator, near_misses })
 }
 
-pub fn part_2(path: &str) -> u32 {
-    let input = preprocess(path);
-    let expression = Regex::new(r"mul\((\d{1,3},\d{1,3})\)|(do)\(\)|(don't)\(\)").unwrap();
+/**
+   The state of the interpreter. If `conditionals` is false, `do()` and `don't()` are ignored
+   and every `mul` instruction counts.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Machine {
+    pub conditionals: bool,
+    pub enabled: bool,
+    pub accumulator: i128,
+}
 
-    let mut sum: u32 = 0;
-    let mut enabled = true;
-    for (_, [capture]) in expression.captures_iter(input.as_str()).map(|c| c.extract()) {
-        match capture {
-            "do" => enabled = true,
-            "don't" => enabled = false,
-            numbers => {
-                if !enabled {
-                    continue;
+impl Machine {
+    pub fn new(conditionals: bool) -> Self {
+        Machine { conditionals, enabled: true, accumulator: 0 }
+    }
+
+    /**
+       Executes a single instruction and updates the state accordingly. Returns what was added
+       to the accumulator, or None if it would overflow, which leaves the state unchanged.
+     */
+    pub fn execute(&mut self, instruction: Instruction) -> Option<i128> {
+        match instruction {
+            Instruction::Mul(first, second) => {
+                if self.enabled || !self.conditionals {
+                    let product = first as i128 * second as i128;
+                    self.accumulator = self.accumulator.checked_add(product)?;
+                    return Some(product);
                 }
+            },
+            Instruction::Do => self.enabled = true,
+            Instruction::Dont => self.enabled = false,
+        }
+
+        Some(0)
+    }
+
+    /**
+       Executes all instructions in order and returns the final accumulator, or None if it
+       overflows
+     */
+    pub fn run(&mut self, tokens: &[Token]) -> Option<i128> {
+        for token in tokens {
+            self.execute(token.instruction)?;
+        }
+
+        Some(self.accumulator)
+    }
+}
+
+/**
+   A recognized instruction, how it was executed and what it added to the accumulator
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub token: Token,
+    /// The instruction as it was written in the input
+    pub text: String,
+    /// Whether `mul` instructions were enabled by `do()` and `don't()` when this one was executed
+    pub enabled: bool,
+    pub contribution: i128,
+}
 
-                // Calculate the product of the two numbers
-                sum += numbers.split(',')
-                    .fold(
-                        1,
-                        |acc: u32, n| acc * n.parse::<u32>().expect("Should be a parsable u32"),
-                    );
+/**
+   A range of the input in which `mul` instructions are either enabled or disabled
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub range: std::ops::Range<usize>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub entries: Vec<TraceEntry>,
+    /// Consecutive, non-empty regions covering the whole input. A region changes at the start of
+    /// the `do()` or `don't()` that changes the state, repeated ones don't start a new region.
+    pub regions: Vec<Region>,
+    pub sum: i128,
+}
+
+/**
+   Executes the input on a new machine and records every instruction, together with the
+   enabled and disabled regions of the input
+ */
+pub fn trace(input: &[u8], conditionals: bool) -> Trace {
+    let mut machine = Machine::new(conditionals);
+    let mut entries = vec![];
+    let mut regions = vec![];
+    let mut region_start = 0;
+
+    let tokens = lex(input, Syntax::default()).into_iter().filter_map(|lexeme| match lexeme {
+        Lexeme::Token(token) => Some(token),
+        Lexeme::NearMiss(_) => None,
+    });
+
+    for token in tokens {
+        let was_enabled = machine.enabled;
+        let contribution = machine.execute(token.instruction).expect("The accumulator overflowed");
+
+        if machine.enabled != was_enabled {
+            if token.offset > region_start {
+                regions.push(Region { range: region_start..token.offset, enabled: was_enabled });
             }
+            region_start = token.offset;
         }
+
+        entries.push(TraceEntry {
+            token,
+            text: String::from_utf8_lossy(&input[token.offset..token.offset + token.length]).into_owned(),
+            enabled: was_enabled,
+            contribution,
+        });
+    }
+
+    if input.len() > region_start {
+        regions.push(Region { range: region_start..input.len(), enabled: machine.enabled });
     }
 
+    Trace { entries, regions, sum: machine.accumulator }
+}
+
+/**
+   Renders the trace of the input file as a table of instructions, followed by the regions
+ */
+pub fn render_trace(path: &str, conditionals: bool) -> String {
+    let input = std::fs::read(path).expect(path);
+    let trace = trace(&input, conditionals);
+    let mut output = String::new();
+
+    for entry in &trace.entries {
+        let state = if entry.enabled { "enabled" } else { "disabled" };
+        output += &format!("{:>8} {:<12} {:<8} {:>+8}\n", entry.token.offset, entry.text, state, entry.contribution);
+    }
+    output += &format!("Sum: {}\n", trace.sum);
+
+    for region in &trace.regions {
+        let state = if region.enabled { "enabled" } else { "disabled" };
+        output += &format!("{:>8}..{:<8} {:<8} {} bytes\n", region.range.start, region.range.end, state, region.range.len());
+    }
+    let disabled: usize = trace.regions.iter().filter(|region| !region.enabled).map(|region| region.range.len()).sum();
+    output += &format!("Disabled: {} of {} bytes\n", disabled, input.len());
+
+    output
+}
+
+/**
+   Renders every near miss of the input file with its text and the reason it was rejected,
+   followed by the number of near misses
+ */
+pub fn render_diagnostics(path: &str, syntax: Syntax) -> String {
+    let input = std::fs::read(path).expect(path);
+    let mut output = String::new();
+    let mut count = 0;
+
+    for lexeme in lex(&input, syntax) {
+        if let Lexeme::NearMiss(near_miss) = lexeme {
+            let text = String::from_utf8_lossy(&input[near_miss.offset..near_miss.offset + near_miss.length]);
+            output += &format!("{:>8} {:<16} {}\n", near_miss.offset, format!("{:?}", text), near_miss.reason);
+            count += 1;
+        }
+    }
+    output += &format!("Near misses: {}\n", count);
+
+    output
+}
+
+pub fn part_1(path: &str) -> i128 {
+    let file = std::fs::File::open(path).expect(path);
+    let sum = run_stream(file, Syntax::default(), false).unwrap_or_else(|error| panic!("{}", error)).sum;
+
+    println!("Result: {}", sum);
+    sum
+}
+
+pub fn part_2(path: &str) -> i128 {
+    let file = std::fs::File::open(path).expect(path);
+    let sum = run_stream(file, Syntax::default(), true).unwrap_or_else(|error| panic!("{}", error)).sum;
+
     println!("Result: {}", sum);
     sum
 }
@@ -63,4 +608,243 @@ mod test {
     fn test_part_2() {
         assert_eq!(part_2("./inputs/day_03_test_part2.txt"), 48);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))");
+
+        assert_eq!(tokens, vec![
+            Token { instruction: Instruction::Mul(2, 4), offset: 1, length: 8 },
+            Token { instruction: Instruction::Dont, offset: 20, length: 7 },
+            Token { instruction: Instruction::Mul(5, 5), offset: 28, length: 8 },
+            Token { instruction: Instruction::Mul(11, 8), offset: 48, length: 9 },
+            Token { instruction: Instruction::Do, offset: 59, length: 4 },
+            Token { instruction: Instruction::Mul(8, 5), offset: 64, length: 8 },
+        ]);
+    }
+
+    #[test]
+    fn test_tokenize_near_misses() {
+        assert!(tokenize("mul(1234,5) mul(1,2 mul ( 2 , 4 ) do( don't").is_empty());
+        assert_eq!(tokenize("mul(mul(1,2)")[0].offset, 4);
+    }
+
+    #[test]
+    fn test_machine() {
+        let mut machine = Machine::new(true);
+        machine.execute(Instruction::Mul(2, 3));
+        machine.execute(Instruction::Dont);
+        machine.execute(Instruction::Mul(4, 5));
+        assert_eq!(machine, Machine { conditionals: true, enabled: false, accumulator: 6 });
+
+        machine.execute(Instruction::Do);
+        machine.execute(Instruction::Mul(1, 1));
+        assert_eq!(machine.accumulator, 7);
+    }
+
+    #[test]
+    fn test_token_stream_chunk_boundaries() {
+        let input = std::fs::read_to_string("./inputs/day_03_test_part2.txt").unwrap();
+        let expected = lex(input.as_bytes(), Syntax::default());
+
+        for chunk_size in 1..=input.len() + 1 {
+            let lexemes: Vec<Lexeme> = TokenStream::with_chunk_size(input.as_bytes(), chunk_size)
+                .map(|lexeme| lexeme.unwrap())
+                .collect();
+            assert_eq!(lexemes, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_token_stream_offsets() {
+        let file = std::fs::File::open("./inputs/day_03.txt").unwrap();
+        let input = std::fs::read_to_string("./inputs/day_03.txt").unwrap();
+
+        for lexeme in TokenStream::with_chunk_size(file, 100) {
+            let Lexeme::Token(token) = lexeme.unwrap() else { continue };
+            let text = &input[token.offset..token.offset + token.length];
+            assert_eq!(tokenize(text), vec![Token { offset: 0, ..token }]);
+        }
+    }
+
+    /**
+       A reader that generates a lot of memory without ever storing it
+     */
+    struct RepeatingReader {
+        pattern: &'static [u8],
+        remaining: usize,
+        position: usize,
+    }
+
+    impl Read for RepeatingReader {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let length = buffer.len().min(self.remaining);
+            for byte in &mut buffer[..length] {
+                *byte = self.pattern[self.position % self.pattern.len()];
+                self.position += 1;
+            }
+
+            self.remaining -= length;
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn test_run_stream() {
+        // 17 bytes per repetition, so the instructions end up at every possible chunk boundary
+        let pattern = b"don't()mul(2,3)do";
+        let reader = RepeatingReader { pattern, remaining: pattern.len() * 10_000, position: 0 };
+        assert_eq!(run_stream(reader, Syntax::default(), false).unwrap().sum, 6 * 10_000);
+
+        let reader = RepeatingReader { pattern: b"mul(1,1)xdo()", remaining: 13 * 5_000, position: 0 };
+        assert_eq!(run_stream(reader, Syntax::default(), true).unwrap().sum, 5_000);
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let trace = trace(input.as_bytes(), true);
+
+        assert_eq!(trace.sum, 48);
+        assert_eq!(trace.entries.len(), 6);
+        assert_eq!(trace.entries[0], TraceEntry {
+            token: Token { instruction: Instruction::Mul(2, 4), offset: 1, length: 8 },
+            text: "mul(2,4)".to_string(),
+            enabled: true,
+            contribution: 8,
+        });
+        assert_eq!(trace.entries[1].text, "don't()");
+        assert_eq!((trace.entries[2].enabled, trace.entries[2].contribution), (false, 0));
+        assert_eq!((trace.entries[5].enabled, trace.entries[5].contribution), (true, 40));
+
+        assert_eq!(trace.regions, vec![
+            Region { range: 0..20, enabled: true },
+            Region { range: 20..59, enabled: false },
+            Region { range: 59..73, enabled: true },
+        ]);
+
+        // Without conditionals, disabled instructions still count
+        let trace = super::trace(input.as_bytes(), false);
+        assert_eq!(trace.sum, 161);
+        assert_eq!((trace.entries[2].enabled, trace.entries[2].contribution), (false, 25));
+    }
+
+    #[test]
+    fn test_trace_regions() {
+        assert_eq!(trace(b"don't()do()don't()", true).regions, vec![
+            Region { range: 0..7, enabled: false },
+            Region { range: 7..11, enabled: true },
+            Region { range: 11..18, enabled: false },
+        ]);
+        assert_eq!(trace(b"do()xdo()", true).regions, vec![Region { range: 0..9, enabled: true }]);
+        assert!(trace(b"", true).regions.is_empty());
+    }
+
+    #[test]
+    fn test_render_trace() {
+        let rendered = render_trace("./inputs/day_03_test_part2.txt", true);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[0], "       1 mul(2,4)     enabled        +8");
+        assert_eq!(lines[6], "Sum: 48");
+        assert_eq!(lines[8], "      20..59       disabled 39 bytes");
+        assert!(lines.last().unwrap().starts_with("Disabled: 39 of "));
+    }
+
+    #[test]
+    fn test_near_misses() {
+        let near_misses = |input: &str, syntax: Syntax| -> Vec<(usize, usize, MissReason)> {
+            lex(input.as_bytes(), syntax).into_iter()
+                .filter_map(|lexeme| match lexeme {
+                    Lexeme::NearMiss(near_miss) => Some((near_miss.offset, near_miss.length, near_miss.reason)),
+                    Lexeme::Token(_) => None,
+                })
+                .collect()
+        };
+        let syntax = Syntax::default();
+
+        assert_eq!(near_misses("mul(4*", syntax), vec![(0, 6, MissReason::Unexpected(b'*'))]);
+        assert_eq!(near_misses("mul ( 2 , 4 )", syntax), vec![(0, 4, MissReason::Whitespace)]);
+        assert_eq!(near_misses("mul(1234,5)", syntax), vec![(0, 8, MissReason::TooManyDigits)]);
+        assert_eq!(near_misses("mul(-2,3)", syntax), vec![(0, 5, MissReason::Sign)]);
+        assert_eq!(near_misses("mul(,3)", syntax), vec![(0, 5, MissReason::MissingOperand)]);
+        assert_eq!(near_misses("xmul(2,3", syntax), vec![(1, 7, MissReason::UnexpectedEnd)]);
+        assert_eq!(near_misses("mul(mul(1,2)", syntax), vec![(0, 5, MissReason::MissingOperand)]);
+        assert!(near_misses("mul[3,7] mulberry mul", syntax).is_empty());
+
+        let lenient = Syntax { max_digits: 4, max_whitespace: 1, signed: true };
+        assert!(near_misses("mul ( 2 , 4 ) mul(1234,-5)", lenient).is_empty());
+        assert_eq!(near_misses("mul(2  ,4)", lenient), vec![(0, 7, MissReason::Whitespace)]);
+    }
+
+    #[test]
+    fn test_lenient_syntax() {
+        let syntax = Syntax { max_digits: 4, max_whitespace: 1, signed: true };
+        let tokens: Vec<Instruction> = lex(b"mul ( 2 , 4 )mul(1234,-5)mul(+7,\n3)", syntax).into_iter()
+            .filter_map(|lexeme| match lexeme {
+                Lexeme::Token(token) => Some(token.instruction),
+                Lexeme::NearMiss(_) => None,
+            })
+            .collect();
+
+        assert_eq!(tokens, vec![Instruction::Mul(2, 4), Instruction::Mul(1234, -5), Instruction::Mul(7, 3)]);
+    }
+
+    #[test]
+    fn test_lenient_chunk_boundaries() {
+        let input = "mul ( 2 , 4 )mul(  1,1)xmul(-12345678,+87654321)mul(4*don't()mul(1 ,1)";
+        let syntax = Syntax { max_digits: 8, max_whitespace: 1, signed: true };
+        let expected = lex(input.as_bytes(), syntax);
+
+        for chunk_size in 1..=input.len() + 1 {
+            let lexemes: Vec<Lexeme> = TokenStream::with_syntax(input.as_bytes(), syntax, chunk_size)
+                .map(|lexeme| lexeme.unwrap())
+                .collect();
+            assert_eq!(lexemes, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_run_stream_report() {
+        let input = "mul(2,4)mul(4*mul ( 2 , 4 )don't()mul(3,3)";
+        let report = run_stream(input.as_bytes(), Syntax::default(), true).unwrap();
+        assert_eq!(report, RunReport { sum: 8, near_misses: 2 });
+
+        let syntax = Syntax { max_whitespace: 1, ..Syntax::default() };
+        let report = run_stream(input.as_bytes(), syntax, false).unwrap();
+        assert_eq!(report, RunReport { sum: 25, near_misses: 1 });
+    }
+
+    #[test]
+    fn test_overflow() {
+        let syntax = Syntax { max_digits: 18, max_whitespace: 0, signed: true };
+        let big = "mul(999999999999999999,999999999999999999)".repeat(171);
+
+        assert!(run_stream(&big.as_bytes()[..42 * 170], syntax, false).is_ok());
+        assert!(matches!(run_stream(big.as_bytes(), syntax, false), Err(RunError::Overflow(offset)) if offset == 42 * 170));
+
+        let mut machine = Machine { accumulator: i128::MIN + 5, ..Machine::new(false) };
+        assert_eq!(machine.execute(Instruction::Mul(-2, 3)), None);
+        assert_eq!(machine.accumulator, i128::MIN + 5);
+    }
+
+    #[test]
+    fn test_render_diagnostics() {
+        let rendered = render_diagnostics("./inputs/day_03_test_part1.txt", Syntax::default());
+        assert_eq!(rendered, "      38 \"mul(32,64]\"     Unexpected ']'\nNear misses: 1\n");
+    }
+
+    #[test]
+    fn test_render_bytes() {
+        // The first near miss ends inside of the 'é', and 0xFF isn't valid UTF-8 at all
+        let rendered = render_diagnostics("./inputs/day_03_test_bytes.txt", Syntax::default());
+        assert_eq!(rendered, concat!(
+            "       0 \"mul(4\u{fffd}\"         Unexpected '\\xC3'\n",
+            "      10 \"mul(2,\u{fffd}\"        Missing operand\n",
+            "Near misses: 2\n",
+        ));
+
+        let rendered = render_trace("./inputs/day_03_test_bytes.txt", true);
+        assert!(rendered.contains("Sum: 9\n"));
+    }
+}
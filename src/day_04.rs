@@ -13,126 +13,343 @@ fn preprocess(path: &str) -> Vec<Vec<char>> {
 }
 
 /**
-   Beginning from (x, y), step through the chars in all eight directions and check if the word
-   "XMAS" can be formed. Returns the number of found matches.
+   The eight directions in which a word can be read, as (x, y) steps
  */
-fn count_xmas_apperances_from(chars: &Vec<Vec<char>>, x: usize, y: usize) -> u32 {
-    if chars[y][x] != 'X' {
-        return 0;
+pub const DIRECTIONS: [(isize, isize); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+/**
+   A single appearance of a word in the letter grid
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordMatch {
+    pub word: String,
+    /// The (x, y) position of the first letter
+    pub start: (usize, usize),
+    /// The (x, y) step from one letter to the next
+    pub direction: (isize, isize),
+    /// The (x, y) positions of all letters in order
+    pub cells: Vec<(usize, usize)>,
+}
+
+/**
+   Returns the position that is `steps` steps away from (x, y) in the given direction. If
+   `wrap_around` is set, positions leaving the grid on one side enter it again on the other side.
+   Otherwise None is returned for positions outside of the grid.
+ */
+fn step_from(chars: &[Vec<char>], x: usize, y: usize, direction: (isize, isize), steps: usize, wrap_around: bool) -> Option<(usize, usize)> {
+    let height = chars.len() as isize;
+    let width = chars[0].len() as isize;
+    let mut current_x = x as isize + direction.0 * steps as isize;
+    let mut current_y = y as isize + direction.1 * steps as isize;
+
+    if wrap_around {
+        current_x = current_x.rem_euclid(width);
+        current_y = current_y.rem_euclid(height);
     }
 
-    // These are the directions that we are going to check
-    let directions: Vec<(i32, i32)> = vec![(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
-    // We already checked that we are starting with an 'X', so we only need to check the remaining characters in this order
-    let expected_characters = vec!['M', 'A', 'S'];
-    // The maximum bounds of the vector
-    let max_x: i32 = (chars[0].len() as i32) - 1;
-    let max_y: i32 = (chars.len() as i32) - 1;
-    let mut sum: u32 = 0;
-
-    'outer: for direction in directions {
-        // Set up the local variables for each direction iteration
-        let mut current_offset = direction.clone();
-
-        for expected_character in &expected_characters {
-            // Find out if we are still in bounds of the vector
-            let current_x: i32 = (x as i32) + current_offset.0;
-            let current_y: i32 = (y as i32) + current_offset.1;
-            if current_x > max_x || current_x < 0 || current_y > max_y || current_y < 0 {
-                // We don't want to use break here, because then the sum would go up
-                continue 'outer;
-            }
+    if current_x < 0 || current_x >= width || current_y < 0 || current_y >= height {
+        return None;
+    }
 
-            // We know now, that we're in bounds, so we can parse the i32 as usize to index the vector
-            if chars[current_y as usize][current_x as usize] != *expected_character {
-                // We don't want to use break here, because then the sum would go up
-                continue 'outer;
-            }
+    Some((current_x as usize, current_y as usize))
+}
 
-            // Go one step further into the currently selected direction
-            current_offset = (current_offset.0 + direction.0, current_offset.1 + direction.1);
+/**
+   Beginning from (x, y), step through the chars in all eight directions and check if any of the
+   words can be formed. Returns all found matches. A word with a single letter is only reported
+   once instead of once per direction.
+ */
+pub fn find_words_from(chars: &[Vec<char>], x: usize, y: usize, words: &[&str], wrap_around: bool) -> Vec<WordMatch> {
+    let mut matches: Vec<WordMatch> = vec![];
+
+    for word in words {
+        let letters: Vec<char> = word.chars().collect();
+        if letters.first() != Some(&chars[y][x]) {
+            continue;
         }
 
-        // Add one appearance when the inner for loop finishes normally, because then all checks worked
-        sum += 1;
+        let directions = if letters.len() == 1 { &DIRECTIONS[..1] } else { &DIRECTIONS[..] };
+
+        'directions: for direction in directions {
+            let mut cells: Vec<(usize, usize)> = vec![];
+
+            for (steps, expected_character) in letters.iter().enumerate() {
+                // Leaving the grid means that the word can't be formed in this direction
+                let Some((current_x, current_y)) = step_from(chars, x, y, *direction, steps, wrap_around) else {
+                    continue 'directions;
+                };
+
+                if chars[current_y][current_x] != *expected_character {
+                    continue 'directions;
+                }
+
+                cells.push((current_x, current_y));
+            }
+
+            matches.push(WordMatch { word: word.to_string(), start: (x, y), direction: *direction, cells });
+        }
     }
 
-    sum
+    matches
 }
 
-pub fn part_1(path: &str) -> u32 {
-    let chars = preprocess(path);
-    let mut sum: u32 = 0;
-
-    for y in 0..chars.len() {
-        let line = &chars[y];
+/**
+   Searches the whole letter grid for the given words in all eight directions. The matches are
+   ordered by their start position, row by row.
+ */
+pub fn find_words(chars: &[Vec<char>], words: &[&str], wrap_around: bool) -> Vec<WordMatch> {
+    let mut matches: Vec<WordMatch> = vec![];
 
+    for (y, line) in chars.iter().enumerate() {
         for x in 0..line.len() {
-            sum += count_xmas_apperances_from(&chars, x, y);
+            matches.extend(find_words_from(chars, x, y, words, wrap_around));
         }
     }
 
+    matches
+}
+
+/**
+   Searches the letter grid of the given input for the given words
+ */
+pub fn search(path: &str, words: &[&str], wrap_around: bool) -> Vec<WordMatch> {
+    let chars = preprocess(path);
+    find_words(&chars, words, wrap_around)
+}
+
+pub fn part_1(path: &str) -> u32 {
+    let sum = search(path, &["XMAS"], false).len() as u32;
+
     println!("XMAS appeared {} times", sum);
     sum
 }
 
 /**
    The slightly different spelling for xmas and x_mas are deliberate, since the task specifically mentioned it differently (XMAS -> X-MAS).
-   Beginning from (x, y), check if the given position results in an X-MAS appearance. An X-MAS appearance are the word MAS in an X-shape.
-   Returns true in case this pattern is detected, and false otherwise.
-   
+   An X-MAS appearance are the word MAS in an X-shape. All other orientations are rotations of this template.
+
    # Examples
    ```
    M . M  |  S . M  |  S . S  |  M . S
    . A .  |  . A .  |  . A .  |  . A .
    S . S  |  S . M  |  M . M  |  M . S
    ```
+ */
+pub const X_MAS_TEMPLATE: &str = "M.S\n.A.\nM.S";
 
-   Upon inspection we notice a pattern. The letters M and S always need to be located in the same row or column and can never
-   appear in the same diagonal together. Additionally, the letter A always needs to be in the center. The function uses these
-   facts to speed up the processing slightly.
+/**
+   The character that matches any letter in a template
  */
-fn is_x_mas_appearance_from(chars: &Vec<Vec<char>>, x: usize, y: usize) -> bool {
-    let max_x = chars[0].len() - 1;
-    let max_y = chars.len() - 1;
-    // The given (x, y) position is on the edge of the word puzzle, so it already can't be a X-MAS appearance
-    let out_of_bounds = x < 1 || x > max_x - 1 || y < 1 || y > max_y - 1;
-
-    if chars[y][x] != 'A' || out_of_bounds {
-        return false;
+pub const WILDCARD: char = '.';
+
+/**
+   Turns the text of a template into a grid of characters. Shorter lines are filled up with
+   wildcards, so that the template is always rectangular.
+ */
+pub fn parse_template(text: &str) -> Vec<Vec<char>> {
+    let mut template: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    let width = template.iter().map(|line| line.len()).max().unwrap_or(0);
+
+    for line in &mut template {
+        line.resize(width, WILDCARD);
     }
 
-    // Define the top-left-bottom-right diagonal chars, and the same for the other diagonal
-    let tlbr_chars = vec![chars[y-1][x-1], chars[y+1][x+1]];
-    let trbl_chars = vec![chars[y-1][x+1], chars[y+1][x-1]];
-    let test_vector_forward = vec!['M', 'S'];
-    let test_vector_backward = vec!['S', 'M'];
-    
-    // Now test that the two diagonals each form the word MAS, forwards or backwards
-    if (tlbr_chars == test_vector_forward || tlbr_chars == test_vector_backward)
-            && (trbl_chars == test_vector_forward || trbl_chars == test_vector_backward)  {
-        return true;
+    template
+}
+
+/**
+   Rotates the template by 90 degrees clockwise
+ */
+fn rotate(template: &[Vec<char>]) -> Vec<Vec<char>> {
+    let height = template.len();
+    let width = template.first().map_or(0, |line| line.len());
+
+    (0..width)
+        .map(|x| (0..height).rev().map(|y| template[y][x]).collect())
+        .collect()
+}
+
+/**
+   Mirrors the template at its vertical axis
+ */
+fn reflect(template: &[Vec<char>]) -> Vec<Vec<char>> {
+    template.iter()
+        .map(|line| line.iter().rev().copied().collect())
+        .collect()
+}
+
+/**
+   Returns the template in all four rotations, and the same for its mirror image. Variants that
+   look the same are only returned once, so a symmetric template yields fewer than eight.
+ */
+pub fn template_variants(template: &[Vec<char>]) -> Vec<Vec<Vec<char>>> {
+    let mut variants: Vec<Vec<Vec<char>>> = vec![];
+
+    for start in [template.to_vec(), reflect(template)] {
+        let mut variant = start;
+        for _ in 0..4 {
+            let next = rotate(&variant);
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+            variant = next;
+        }
     }
 
-    return false;
+    variants
 }
 
-pub fn part_2(path: &str) -> u32 {
-    let chars = preprocess(path);
-    let mut sum: u32 = 0;
+/**
+   A single placement of a template variant in the letter grid
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateMatch {
+    /// The index of the matching variant, as returned by `template_variants`
+    pub variant: usize,
+    /// The (x, y) position of the top left corner of the variant
+    pub position: (usize, usize),
+    /// The (x, y) positions of all cells that aren't wildcards
+    pub cells: Vec<(usize, usize)>,
+}
 
-    for y in 0..chars.len() {
-        let line = &chars[y];
+/**
+   Returns true if the variant matches the grid with its top left corner at (x, y)
+ */
+fn template_matches_at(chars: &[Vec<char>], variant: &[Vec<char>], x: usize, y: usize) -> bool {
+    variant.iter().enumerate().all(|(template_y, line)| {
+        line.iter().enumerate().all(|(template_x, expected_character)| {
+            *expected_character == WILDCARD
+                || chars.get(y + template_y).and_then(|row| row.get(x + template_x)) == Some(expected_character)
+        })
+    })
+}
 
-        for x in 0..line.len() {
-            sum += if is_x_mas_appearance_from(&chars, x, y) { 1 } else { 0 };
+/**
+   Finds every placement of every rotation and reflection of the template in the letter grid. The
+   matches are ordered by variant, and then by position row by row. A placement is reported once
+   per matching variant.
+ */
+pub fn find_template(chars: &[Vec<char>], template: &[Vec<char>]) -> Vec<TemplateMatch> {
+    let mut matches: Vec<TemplateMatch> = vec![];
+
+    for (index, variant) in template_variants(template).iter().enumerate() {
+        let variant_height = variant.len();
+        let variant_width = variant.first().map_or(0, |line| line.len());
+
+        for y in 0..(chars.len() + 1).saturating_sub(variant_height) {
+            for x in 0..(chars[y].len() + 1).saturating_sub(variant_width) {
+                if !template_matches_at(chars, variant, x, y) {
+                    continue;
+                }
+
+                let cells = variant.iter().enumerate()
+                    .flat_map(|(template_y, line)| line.iter().enumerate()
+                        .filter(|(_, c)| **c != WILDCARD)
+                        .map(move |(template_x, _)| (x + template_x, y + template_y)))
+                    .collect();
+                matches.push(TemplateMatch { variant: index, position: (x, y), cells });
+            }
         }
     }
 
+    matches
+}
+
+pub fn part_2(path: &str) -> u32 {
+    let chars = preprocess(path);
+    let sum = find_template(&chars, &parse_template(X_MAS_TEMPLATE)).len() as u32;
+
     println!("X-MAS appeared {} times", sum);
     sum
 }
 
+/**
+   Anything that covers cells of the letter grid and can be highlighted
+ */
+pub trait MatchCells {
+    fn cells(&self) -> &[(usize, usize)];
+}
+
+impl MatchCells for WordMatch {
+    fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+}
+
+impl MatchCells for TemplateMatch {
+    fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+}
+
+/**
+   How matched cells are set apart from the rest of the grid
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HighlightStyle {
+    /// Matched letters are printed bold and red, all other letters stay as they are
+    Ansi,
+    /// All letters that aren't matched are replaced by '.', just like in the puzzle text.
+    /// This is plain text, so it is suitable for comparisons in tests.
+    Dots,
+}
+
+const ANSI_HIGHLIGHT: &str = "\x1b[1;31m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/**
+   Renders the letter grid with the cells of the matches highlighted in the given style, one line
+   per row. If `only` is set, just the match with that index is highlighted.
+ */
+pub fn render_matches<M: MatchCells>(chars: &[Vec<char>], matches: &[M], style: HighlightStyle, only: Option<usize>) -> String {
+    let mut highlighted: Vec<Vec<bool>> = chars.iter().map(|line| vec![false; line.len()]).collect();
+
+    for (index, highlight_match) in matches.iter().enumerate() {
+        if only.is_some_and(|only| only != index) {
+            continue;
+        }
+
+        for (x, y) in highlight_match.cells() {
+            highlighted[*y][*x] = true;
+        }
+    }
+
+    let mut rendered = String::new();
+
+    for (line, highlighted_line) in chars.iter().zip(&highlighted) {
+        for (c, is_highlighted) in line.iter().zip(highlighted_line) {
+            match (style, is_highlighted) {
+                (HighlightStyle::Ansi, true) => rendered.push_str(&format!("{}{}{}", ANSI_HIGHLIGHT, c, ANSI_RESET)),
+                (HighlightStyle::Dots, false) => rendered.push('.'),
+                _ => rendered.push(*c),
+            }
+        }
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+/**
+   Renders the letter grid once for every single match, separated by empty lines
+ */
+pub fn render_each_match<M: MatchCells>(chars: &[Vec<char>], matches: &[M], style: HighlightStyle) -> String {
+    (0..matches.len())
+        .map(|index| render_matches(chars, matches, style, Some(index)))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/**
+   Searches the letter grid of the given input for the given words, and renders all matches
+ */
+pub fn render_search(path: &str, words: &[&str], style: HighlightStyle) -> String {
+    let chars = preprocess(path);
+    let matches = find_words(&chars, words, false);
+
+    render_matches(&chars, &matches, style, None)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -143,10 +360,10 @@ mod test {
             vec!['X', 'M', 'A', 'S']
         ];
 
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 0), 1);
-        assert_eq!(count_xmas_apperances_from(&chars, 1, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 2, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 3, 0), 0);
+        assert_eq!(find_words_from(&chars, 0, 0, &["XMAS"], false).len(), 1);
+        assert_eq!(find_words_from(&chars, 1, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 2, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 3, 0, &["XMAS"], false).len(), 0);
     }
 
     #[test]
@@ -155,13 +372,13 @@ mod test {
             vec!['S', 'A', 'M', 'X', 'M', 'A', 'S']
         ];
 
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 1, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 2, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 3, 0), 2);
-        assert_eq!(count_xmas_apperances_from(&chars, 4, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 5, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 6, 0), 0);
+        assert_eq!(find_words_from(&chars, 0, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 1, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 2, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 3, 0, &["XMAS"], false).len(), 2);
+        assert_eq!(find_words_from(&chars, 4, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 5, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 6, 0, &["XMAS"], false).len(), 0);
     }
 
     #[test]
@@ -173,10 +390,10 @@ mod test {
             vec!['S'],
         ];
 
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 0), 1);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 1), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 2), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 3), 0);
+        assert_eq!(find_words_from(&chars, 0, 0, &["XMAS"], false).len(), 1);
+        assert_eq!(find_words_from(&chars, 0, 1, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 2, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 3, &["XMAS"], false).len(), 0);
     }
 
     #[test]
@@ -191,13 +408,13 @@ mod test {
             vec!['S'],
         ];
 
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 0), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 1), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 2), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 3), 2);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 4), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 5), 0);
-        assert_eq!(count_xmas_apperances_from(&chars, 0, 6), 0);
+        assert_eq!(find_words_from(&chars, 0, 0, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 1, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 2, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 3, &["XMAS"], false).len(), 2);
+        assert_eq!(find_words_from(&chars, 0, 4, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 5, &["XMAS"], false).len(), 0);
+        assert_eq!(find_words_from(&chars, 0, 6, &["XMAS"], false).len(), 0);
     }
 
     #[test]
@@ -213,11 +430,11 @@ mod test {
             let line = &chars[y];
             for x in 0..line.len() {
                 if x == 0 && y == 0 {
-                    assert_eq!(count_xmas_apperances_from(&chars, x, y), 1);
+                    assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 1);
                     continue;
                 }
 
-                assert_eq!(count_xmas_apperances_from(&chars, x, y), 0);
+                assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 0);
             }
         }
     }
@@ -238,11 +455,11 @@ mod test {
             let line = &chars[y];
             for x in 0..line.len() {
                 if x == 3 && y == 3 {
-                    assert_eq!(count_xmas_apperances_from(&chars, x, y), 8);
+                    assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 8);
                     continue;
                 }
 
-                assert_eq!(count_xmas_apperances_from(&chars, x, y), 0);
+                assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 0);
             }
         }
     }
@@ -260,11 +477,11 @@ mod test {
             let line = &chars[y];
             for x in 0..line.len() {
                 if x == 0 && y == 0 {
-                    assert_eq!(count_xmas_apperances_from(&chars, x, y), 3);
+                    assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 3);
                     continue;
                 }
 
-                assert_eq!(count_xmas_apperances_from(&chars, x, y), 0);
+                assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 0);
             }
         }
     }
@@ -281,7 +498,7 @@ mod test {
         for y in 0..chars.len() {
             let line = &chars[y];
             for x in 0..line.len() {
-                assert_eq!(count_xmas_apperances_from(&chars, x, y), 0);
+                assert_eq!(find_words_from(&chars, x, y, &["XMAS"], false).len(), 0);
             }
         }
     }
@@ -293,18 +510,12 @@ mod test {
 
     #[test]
     fn test_x_mas_simple() {
+        let template = parse_template(X_MAS_TEMPLATE);
         let assertions = |chars: &Vec<Vec<char>>| {
-            for y in 0..chars.len() {
-                let line = &chars[y];
-                for x in 0..line.len() {
-                    if x == 1 && y == 1 {
-                        assert!(is_x_mas_appearance_from(chars, x, y));
-                        continue;
-                    }
-
-                    assert!(!is_x_mas_appearance_from(chars, x, y));
-                }
-            }
+            let positions: Vec<(usize, usize)> = find_template(chars, &template).iter()
+                .map(|template_match| template_match.position)
+                .collect();
+            assert_eq!(positions, vec![(0, 0)]);
         };
 
         let chars = vec![
@@ -344,21 +555,109 @@ mod test {
             vec!['M', 'M', 'M', 'S'],
         ];
 
-        for y in 0..chars.len() {
-            let line = &chars[y];
-            for x in 0..line.len() {
-                if (x == 1 && y == 1) || (x == 2 && y == 1) {
-                    assert!(is_x_mas_appearance_from(&chars, x, y));
-                    continue;
-                }
-
-                assert!(!is_x_mas_appearance_from(&chars, x, y));
-            }
-        }
+        let mut positions: Vec<(usize, usize)> = find_template(&chars, &parse_template(X_MAS_TEMPLATE)).iter()
+            .map(|template_match| template_match.position)
+            .collect();
+        positions.sort();
+        assert_eq!(positions, vec![(0, 0), (1, 0)]);
     }
 
     #[test]
     fn test_part_2() {
         assert_eq!(part_2("./inputs/day_04_test.txt"), 9);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_find_words() {
+        let chars = preprocess("./inputs/day_04_test.txt");
+        let matches = find_words(&chars, &["XMAS", "SAMX"], false);
+
+        // Every XMAS is also a SAMX read backwards
+        assert_eq!(matches.len(), 36);
+        assert_eq!(matches[0], WordMatch {
+            word: "XMAS".to_string(),
+            start: (4, 0),
+            direction: (1, 1),
+            cells: vec![(4, 0), (5, 1), (6, 2), (7, 3)],
+        });
+    }
+
+    #[test]
+    fn test_find_words_wrap_around() {
+        let chars = vec![
+            vec!['A', 'S', '.', 'X', 'M'],
+            vec!['.', '.', '.', '.', '.'],
+        ];
+
+        assert!(find_words(&chars, &["XMAS"], false).is_empty());
+        let matches = find_words(&chars, &["XMAS"], true);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].cells, vec![(3, 0), (4, 0), (0, 0), (1, 0)]);
+
+        assert_eq!(find_words(&chars, &["S"], false).len(), 1);
+    }
+
+    #[test]
+    fn test_template_variants() {
+        let x_mas = parse_template(X_MAS_TEMPLATE);
+        let variants = template_variants(&x_mas);
+
+        assert_eq!(variants.len(), 4);
+        assert_eq!(variants[1], parse_template("M.M\n.A.\nS.S"));
+
+        let corner = parse_template("AB\nC");
+        assert_eq!(corner, vec![vec!['A', 'B'], vec!['C', '.']]);
+        assert_eq!(template_variants(&corner).len(), 8);
+        assert_eq!(template_variants(&parse_template("A.\n.A")).len(), 2);
+    }
+
+    #[test]
+    fn test_find_template() {
+        let chars = vec![
+            vec!['X', 'M', '.', '.'],
+            vec!['.', 'A', '.', 'X'],
+            vec!['.', '.', 'A', 'M'],
+        ];
+
+        let matches = find_template(&chars, &parse_template("XM\n.A"));
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0], TemplateMatch { variant: 0, position: (0, 0), cells: vec![(0, 0), (1, 0), (1, 1)] });
+
+        let rotated = template_variants(&parse_template("XM\n.A"));
+        assert_eq!(rotated[matches[1].variant], parse_template(".X\nAM"));
+        assert_eq!(matches[1].position, (2, 1));
+    }
+
+    #[test]
+    fn test_render_search() {
+        assert_eq!(render_search("./inputs/day_04_test.txt", &["XMAS"], HighlightStyle::Dots), concat!(
+            "....XXMAS.\n",
+            ".SAMXMS...\n",
+            "...S..A...\n",
+            "..A.A.MS.X\n",
+            "XMASAMX.MM\n",
+            "X.....XA.A\n",
+            "S.S.S.S.SS\n",
+            ".A.A.A.A.A\n",
+            "..M.M.M.MM\n",
+            ".X.X.XMASX\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_matches() {
+        let chars = vec![
+            vec!['X', 'M', 'A', 'S'],
+            vec!['M', 'A', 'S', 'X'],
+        ];
+        let matches = find_words(&chars, &["XMAS"], false);
+
+        assert_eq!(render_matches(&chars, &matches, HighlightStyle::Dots, None), "XMAS\n....\n");
+        assert_eq!(render_matches(&chars, &matches, HighlightStyle::Dots, Some(1)), "....\n....\n");
+        assert_eq!(render_matches(&chars, &matches, HighlightStyle::Ansi, None),
+            "\x1b[1;31mX\x1b[0m\x1b[1;31mM\x1b[0m\x1b[1;31mA\x1b[0m\x1b[1;31mS\x1b[0m\nMASX\n");
+
+        let template_matches = find_template(&chars, &parse_template("MA\n.S"));
+        assert_eq!(render_each_match(&chars, &template_matches, HighlightStyle::Dots), ".MA.\n..S.\n\n.M..\n.AS.\n");
+    }
+}
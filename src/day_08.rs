@@ -34,86 +34,130 @@ fn preprocess(path: &str) -> (usize, usize, HashMap<char, Vec<(usize, usize)>>)
 }
 
 /**
-   Computes the antinodes of two positions. Doesn't check for bounds, instead it just returns the
-   two possible isize positions, which of course might be negative.
+   Describes where two antennas of the same frequency create antinodes. All antinodes lie on the
+   line through both antennas.
  */
-fn get_antinodes_of(first: (usize, usize), second: (usize, usize)) -> ((isize, isize), (isize, isize)) {
-    let f = (first.0 as isize, first.1 as isize);
-    let s = (second.0 as isize, second.1 as isize);
-    let diff = (s.0 - f.0, s.1 - f.1);
-
-    ((f.0 - diff.0, f.1 - diff.1), (s.0 + diff.0, s.1 + diff.1))
+#[derive(Debug, Clone, PartialEq)]
+pub struct AntinodeRule {
+    /// A point is an antinode if it is k times as far from one antenna as from the other, for
+    /// any k of the ratios
+    pub ratios: Vec<u32>,
+    /// Whether the points between the two antennas count
+    pub interior: bool,
+    /// Whether the points beyond the two antennas count
+    pub exterior: bool,
+    /// Whether the antennas themselves count, and every antinode repeats in steps of the distance
+    /// between the antennas in both directions
+    pub repeat: bool,
 }
 
-pub fn part_1(path: &str) -> u32 {
-    let (max_x_u, max_y_u, map) = preprocess(path);
-    let max_x = max_x_u as isize;
-    let max_y = max_y_u as isize;
-    let mut all_antinodes: Vec<(isize, isize)> = vec![];
+impl AntinodeRule {
+    /**
+       The antinodes of part 1, which are twice as far from one antenna as from the other
+     */
+    pub fn part_1() -> Self {
+        AntinodeRule { ratios: vec![2], interior: false, exterior: true, repeat: false }
+    }
 
-    for values in map.values() {
-        // The range already guarantees that we stay in bounds. If the values only contain one
-        // antenna positions, the 'j' loop will never run, and the 'i' loop will terminate after
-        // the first iteration.
-        for i in 0..values.len() {
-            for j in i+1..values.len() {
-                let i_j_antinodes = get_antinodes_of(values[i], values[j]);
-                all_antinodes.push(i_j_antinodes.0);
-                all_antinodes.push(i_j_antinodes.1);
+    /**
+       The resonant antinodes of part 2, which are the antennas and all points reached from them
+       by repeatedly stepping the distance between them
+     */
+    pub fn part_2() -> Self {
+        AntinodeRule { ratios: vec![], interior: false, exterior: false, repeat: true }
+    }
+
+    /**
+       Returns the antinodes as fractions (numerator, denominator) of the way from the first to
+       the second antenna. So 0 is the first antenna, 1 the second one.
+
+       With the distances |t| and |t - 1| to both antennas, a ratio k is met at t = k / (k - 1)
+       and t = -1 / (k - 1) beyond the antennas, and at t = k / (k + 1) and t = 1 / (k + 1)
+       between them.
+     */
+    fn parameters(&self) -> Vec<(isize, isize)> {
+        let mut parameters = vec![];
+
+        for &k in &self.ratios {
+            let k = k as isize;
+            if self.exterior && k >= 2 {
+                parameters.push((k, k - 1));
+                parameters.push((-1, k - 1));
             }
+            if self.interior && k >= 1 {
+                parameters.push((k, k + 1));
+                parameters.push((1, k + 1));
+            }
+        }
+
+        if self.repeat {
+            parameters.push((0, 1));
+            parameters.push((1, 1));
         }
+
+        parameters
     }
+}
 
-    all_antinodes.sort();
-    let mut in_bound_antinodes: Vec<&(isize, isize)> = all_antinodes.iter()
-        .filter(|antinode| antinode.0 >= 0 && antinode.0 <= max_x
-            && antinode.1 >= 0 && antinode.1 <= max_y)
-        .collect();
-    in_bound_antinodes.dedup();
-
-    println!("Found {} unique antinode positions", in_bound_antinodes.len());
-    in_bound_antinodes.len() as u32
+/**
+   Computes the position at the fraction of the difference from the first antenna. Doesn't check
+   for bounds, but returns None if the position isn't an integer lattice point.
+ */
+fn position_at(first: (isize, isize), diff: (isize, isize), (numerator, denominator): (isize, isize)) -> Option<(isize, isize)> {
+    let x = diff.0 * numerator;
+    let y = diff.1 * numerator;
+    if x % denominator != 0 || y % denominator != 0 {
+        return None;
+    }
+
+    Some((first.0 + x / denominator, first.1 + y / denominator))
 }
 
 /**
-   Computes the resonant antinodes of two positions. The two positions themselves
-   are also included in the resulting positions, since the two positions resonate
-   with each other. All returned positions are guaranteed to stay in bounds.
+   Computes the antinodes that the rule creates for two antennas. All returned positions are
+   guaranteed to stay in bounds, and are sorted without duplicates.
  */
-fn get_resonant_antinodes_of(first: (usize, usize), second: (usize, usize), max_x: isize, max_y: isize) -> Vec<(usize, usize)> {
+fn antinodes_of(first: (usize, usize), second: (usize, usize), rule: &AntinodeRule, max_x: isize, max_y: isize) -> Vec<(usize, usize)> {
     let f = (first.0 as isize, first.1 as isize);
     let s = (second.0 as isize, second.1 as isize);
     let diff = (s.0 - f.0, s.1 - f.1);
+    let in_bounds = |position: &(isize, isize)| position.0 >= 0 && position.0 <= max_x
+        && position.1 >= 0 && position.1 <= max_y;
 
     let mut results: Vec<(usize, usize)> = vec![];
 
-    // ((f.0 - diff.0, f.1 - diff.1), (s.0 + diff.0, s.1 + diff.1))
-    let mut current_modified_first = f.clone();
-
-    // As long as the modifications stay in bounds, add the modified position to the results
-    while current_modified_first.0 >= 0 && current_modified_first.0 <= max_x
-            && current_modified_first.1 >= 0 && current_modified_first.1 <= max_y {
-        results.push((current_modified_first.0 as usize, current_modified_first.1 as usize));
-        current_modified_first = (current_modified_first.0 - diff.0, current_modified_first.1 - diff.1);
-    }
+    for (numerator, denominator) in rule.parameters() {
+        if !rule.repeat {
+            if let Some(position) = position_at(f, diff, (numerator, denominator)).filter(in_bounds) {
+                results.push((position.0 as usize, position.1 as usize));
+            }
+            continue;
+        }
 
-    let mut current_modified_second = s.clone();
+        // Start between the antennas, which is always in bounds, and walk outwards in both
+        // directions until the bounds are left. Stepping keeps a position on the grid or off it.
+        let start = numerator.rem_euclid(denominator);
+        for direction in [1, -1] {
+            let mut step = if direction == 1 { 0 } else { -1 };
 
-    // As long as the modifications stay in bounds, add the modified position to the results
-    while current_modified_second.0 >= 0 && current_modified_second.0 <= max_x
-            && current_modified_second.1 >= 0 && current_modified_second.1 <= max_y {
-        results.push((current_modified_second.0 as usize, current_modified_second.1 as usize));
-        current_modified_second = (current_modified_second.0 + diff.0, current_modified_second.1 + diff.1);
+            while let Some(position) = position_at(f, diff, (start + step * denominator, denominator)).filter(in_bounds) {
+                results.push((position.0 as usize, position.1 as usize));
+                step += direction;
+            }
+        }
     }
 
+    results.sort();
+    results.dedup();
     results
 }
 
-pub fn part_2(path: &str) -> u32 {
-    let (max_x_u, max_y_u, map) = preprocess(path);
-    let max_x = max_x_u as isize;
-    let max_y = max_y_u as isize;
-
+/**
+   Counts the unique positions in bounds that are antinodes of any two antennas with the same
+   frequency
+ */
+pub fn count_antinodes(path: &str, rule: &AntinodeRule) -> u32 {
+    let (max_x, max_y, map) = preprocess(path);
     let mut all_antinodes: Vec<(usize, usize)> = vec![];
 
     for values in map.values() {
@@ -122,7 +166,7 @@ pub fn part_2(path: &str) -> u32 {
         // the first iteration.
         for i in 0..values.len() {
             for j in i+1..values.len() {
-                let mut i_j_antinodes = get_resonant_antinodes_of(values[i], values[j], max_x, max_y);
+                let mut i_j_antinodes = antinodes_of(values[i], values[j], rule, max_x as isize, max_y as isize);
                 all_antinodes.append(&mut i_j_antinodes);
             }
         }
@@ -130,14 +174,27 @@ pub fn part_2(path: &str) -> u32 {
 
     all_antinodes.sort();
     all_antinodes.dedup();
-
-    println!("Found {} unique resonant antinode positions", all_antinodes.len());
     all_antinodes.len() as u32
 }
 
+pub fn part_1(path: &str) -> u32 {
+    let count = count_antinodes(path, &AntinodeRule::part_1());
+
+    println!("Found {} unique antinode positions", count);
+    count
+}
+
+pub fn part_2(path: &str) -> u32 {
+    let count = count_antinodes(path, &AntinodeRule::part_2());
+
+    println!("Found {} unique resonant antinode positions", count);
+    count
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_preprocess() {
@@ -150,23 +207,22 @@ mod test {
 
     #[test]
     fn test_get_antinodes_of_normal_positions() {
-        let antinodes = get_antinodes_of((8, 8), (9, 9));
-        assert!(antinodes.0 == (7, 7));
-        assert!(antinodes.1 == (10, 10));
+        let antinodes = antinodes_of((8, 8), (9, 9), &AntinodeRule::part_1(), 20, 20);
+        assert_eq!(antinodes, vec![(7, 7), (10, 10)]);
     }
 
     #[test]
     fn test_get_antinodes_out_of_bounds() {
-        let antinodes = get_antinodes_of((1, 1), (3, 3));
-        assert!(antinodes.0 == (-1, -1));
-        assert!(antinodes.1 == (5, 5));
+        let antinodes = antinodes_of((1, 1), (3, 3), &AntinodeRule::part_1(), 20, 20);
+        assert_eq!(antinodes, vec![(5, 5)]);
+        // The other antinode lies outside of the map
+        assert_eq!(position_at((1, 1), (2, 2), (-1, 1)), Some((-1, -1)));
     }
 
     #[test]
     fn test_get_antinode_reverse() {
-        let antinodes = get_antinodes_of((9, 9), (8, 8));
-        assert!(antinodes.0 == (10, 10));
-        assert!(antinodes.1 == (7, 7));
+        let antinodes = antinodes_of((9, 9), (8, 8), &AntinodeRule::part_1(), 20, 20);
+        assert_eq!(antinodes, antinodes_of((8, 8), (9, 9), &AntinodeRule::part_1(), 20, 20));
     }
 
     #[test]
@@ -177,14 +233,14 @@ mod test {
 
     #[test]
     fn get_resonant_antinodes_of_normal_positions() {
-        let antinodes = get_resonant_antinodes_of((0, 0), (1, 1), 3, 3);
-        assert_eq!(antinodes, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+        let antinodes: HashSet<(usize, usize)> = antinodes_of((0, 0), (1, 1), &AntinodeRule::part_2(), 3, 3).into_iter().collect();
+        assert_eq!(antinodes, HashSet::from([(0, 0), (1, 1), (2, 2), (3, 3)]));
     }
 
     #[test]
     fn get_resonant_antinodes_of_more_complex_positions() {
-        let antinodes = get_resonant_antinodes_of( (4, 6), (3, 4), 8, 10);
-        assert_eq!(antinodes, vec![(4, 6), (5, 8), (6, 10), (3, 4), (2, 2), (1, 0)]);
+        let antinodes: HashSet<(usize, usize)> = antinodes_of( (4, 6), (3, 4), &AntinodeRule::part_2(), 8, 10).into_iter().collect();
+        assert_eq!(antinodes, HashSet::from([(4, 6), (5, 8), (6, 10), (3, 4), (2, 2), (1, 0)]));
     }
 
     #[test]
@@ -192,4 +248,48 @@ mod test {
         let result = part_2("./inputs/day_08_test.txt");
         assert_eq!(result, 34);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_interior_antinodes() {
+        let rule = AntinodeRule { ratios: vec![2], interior: true, exterior: false, repeat: false };
+        assert_eq!(antinodes_of((0, 0), (3, 3), &rule, 9, 9), vec![(1, 1), (2, 2)]);
+        // A third of the way isn't on the grid
+        assert_eq!(antinodes_of((0, 0), (1, 2), &rule, 9, 9), vec![]);
+
+        let midpoint = AntinodeRule { ratios: vec![1], interior: true, exterior: true, repeat: false };
+        assert_eq!(antinodes_of((2, 0), (2, 4), &midpoint, 9, 9), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn test_multiple_ratios() {
+        let rule = AntinodeRule { ratios: vec![2, 3], interior: true, exterior: true, repeat: false };
+        // t = 2, -1 for k = 2 and t = 3/2, -1/2, 3/4, 1/4 for k = 3. A third isn't on the grid.
+        assert_eq!(antinodes_of((4, 4), (8, 4), &rule, 20, 20), vec![
+            (0, 4), (2, 4), (5, 4), (7, 4), (10, 4), (12, 4),
+        ]);
+    }
+
+    #[test]
+    fn test_repeated_interior_antinodes() {
+        let rule = AntinodeRule { ratios: vec![1], interior: true, exterior: false, repeat: true };
+        assert_eq!(antinodes_of((2, 0), (4, 0), &rule, 7, 0), vec![
+            (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_count_antinodes() {
+        assert_eq!(count_antinodes("./inputs/day_08_test.txt", &AntinodeRule::part_1()), 14);
+        assert_eq!(count_antinodes("./inputs/day_08_test.txt", &AntinodeRule::part_2()), 34);
+
+        let interior = AntinodeRule { ratios: vec![2], interior: true, exterior: false, repeat: false };
+        let both = AntinodeRule { ratios: vec![2], interior: true, exterior: true, repeat: false };
+        // No pair of antennas of the example is a multiple of 3 apart in both directions
+        assert_eq!(count_antinodes("./inputs/day_08_test.txt", &interior), 0);
+        assert_eq!(count_antinodes("./inputs/day_08_test.txt", &both), 14);
+
+        // The thirds between (0, 0) and (6, 3), and between (1, 8) and (4, 8), plus (7, 8) beyond
+        assert_eq!(count_antinodes("./inputs/day_08_test_interior.txt", &interior), 4);
+        assert_eq!(count_antinodes("./inputs/day_08_test_interior.txt", &both), 5);
+    }
+}
@@ -3,12 +3,21 @@
  */
 fn preprocess(path: &str) -> Vec<i32> {
     let contents = std::fs::read_to_string(path).expect(path);
+    parse_disk_map(&contents)
+}
+
+/**
+   Turns a dense disk map into the expanded file block format. Every even digit is the size of
+   a file, every odd digit the size of the free space following it. Files get their id by
+   their position in the map. Trailing whitespace, like the final newline of an input file,
+   is ignored.
+ */
+pub fn parse_disk_map(disk_map: &str) -> Vec<i32> {
     let mut result: Vec<i32> = vec![];
-    let chars: Vec<char> = contents.chars().collect();
     let mut current_id = 0;
 
-    for i in 0..chars.len() {
-        let block_size = chars[i].to_digit(10).expect("Should be a digit in base 10");
+    for (i, c) in disk_map.trim_end().chars().enumerate() {
+        let block_size = c.to_digit(10).expect("Should be a digit in base 10");
         let append;
         if i % 2 == 0 {
             append = current_id;
@@ -26,67 +35,176 @@ fn preprocess(path: &str) -> Vec<i32> {
 }
 
 /**
-   Moves the blocks around with the given rules and then returns the new computed checksum
+   Appends a free span of the given size to the disk map, which must currently expect a free
+   digit. Spans longer than 9 blocks are continued behind empty files, each of which uses up
+   a file id. Afterwards the disk map expects a file digit.
  */
-pub fn part_1(path: &str) -> u128 {
-    let mut storage = preprocess(path);
+fn push_free_span(disk_map: &mut String, next_id: &mut i32, mut size: usize) {
+    while size > 9 {
+        disk_map.push('9');
+        disk_map.push('0');
+        *next_id += 1;
+        size -= 9;
+    }
 
-    let mut left_pointer = 0;
-    let mut right_pointer = storage.len() - 1;
+    disk_map.push(char::from_digit(size as u32, 10).expect("Should be a digit in base 10"));
+}
 
-    let mut checksum: u128 = 0;
+/**
+   Turns the expanded storage back into a dense disk map. If `renumber` is false, every file
+   keeps its id, which only works if the ids increase from left to right and no file is longer
+   than 9 blocks. Otherwise None is returned. If `renumber` is true, the files are numbered by
+   their position instead, and long files are split into several files.
+ */
+fn write_disk_map(storage: &[i32], renumber: bool) -> Option<String> {
+    let mut disk_map = String::new();
+    let mut next_id = 0;
+    let mut pending_free = 0;
+    let mut expects_file = true;
+
+    for (id, start, size) in files_of(storage) {
+        let free_before = start - pending_free;
+
+        // Close the previous file with the free space behind it. If the disk starts with
+        // free space, an empty first file is needed in front of it.
+        if expects_file && free_before > 0 {
+            disk_map.push('0');
+            next_id += 1;
+            expects_file = false;
+        }
+        if !expects_file {
+            push_free_span(&mut disk_map, &mut next_id, free_before);
+        }
 
-    while left_pointer < storage.len() {
-        // In this case all blocks to the left are already filled, and all blocks
-        // to the right should be empty. Just compute the checksum now.
-        if left_pointer >= right_pointer {
-            let block_value = storage[left_pointer];
+        if !renumber {
+            // Skip unused ids with empty files without free space between them
+            while next_id < id {
+                disk_map.push_str("00");
+                next_id += 1;
+            }
 
-            if block_value > 0 {
-                checksum += (left_pointer as u128) * (storage[left_pointer] as u128);
+            if next_id > id || size > 9 {
+                return None;
             }
+        }
+
+        // Long files are split into several files without free space between them
+        let mut remaining = size;
+        while remaining > 9 {
+            disk_map.push_str("90");
+            next_id += 1;
+            remaining -= 9;
+        }
+        disk_map.push(char::from_digit(remaining as u32, 10).expect("Should be a digit in base 10"));
+        next_id += 1;
+
+        pending_free = start + size;
+        expects_file = false;
+    }
+
+    let trailing_free = storage.len() - pending_free;
+    if trailing_free > 0 {
+        if expects_file {
+            disk_map.push('0');
+        }
+        push_free_span(&mut disk_map, &mut next_id, trailing_free);
+    }
+
+    Some(disk_map)
+}
+
+/**
+   Turns the expanded storage back into the dense disk map format read by `parse_disk_map`,
+   keeping all file ids. Parsing the result yields the same storage again. Returns None if
+   the ids can't be expressed in the format, which is the case if a file with a lower id
+   lies behind a file with a higher id, or if a file is longer than 9 blocks. A free gap
+   longer than 9 blocks returns None as well, unless enough ids are unused before the next
+   file, because each further 9 blocks of the gap need an empty file.
+ */
+pub fn serialize_disk_map(storage: &[i32]) -> Option<String> {
+    write_disk_map(storage, false)
+}
 
+/**
+   Turns the expanded storage back into the dense disk map format read by `parse_disk_map`.
+   The layout of the blocks is kept, but the files are renumbered from left to right, so this
+   works for every storage, e.g. a compacted one. The checksum of the parsed result will
+   differ from the original one if the ids had to be changed.
+ */
+pub fn serialize_disk_map_renumbered(storage: &[i32]) -> String {
+    write_disk_map(storage, true).expect("Renumbering never fails")
+}
+
+/**
+   Moves single blocks from the end of the storage into the leftmost free blocks, until
+   all files are packed to the left. The storage is modified in place. Returns the number
+   of moved blocks.
+ */
+fn compact_blocks(storage: &mut [i32]) -> usize {
+    if storage.is_empty() {
+        return 0;
+    }
+
+    let mut left_pointer = 0;
+    let mut right_pointer = storage.len() - 1;
+    let mut moved_blocks = 0;
+
+    while left_pointer < right_pointer {
+        // Advance the left pointer until we find the next free block
+        if storage[left_pointer] >= 0 {
             left_pointer += 1;
             continue;
         }
 
-        // If the left block is free and the right block is occupied, swap them.
-        // In this case we also need to advance the right pointer
-        if storage[left_pointer] < 0 {
-            // Advance the right pointer until we find the next filled block
-            while storage[right_pointer] < 0 {
-                right_pointer -= 1;
-            }
-
-            storage[left_pointer] = storage[right_pointer];
-            storage[right_pointer] = -1;
+        // Advance the right pointer until we find the next filled block
+        if storage[right_pointer] < 0 {
             right_pointer -= 1;
+            continue;
         }
 
-        // At this point the block at left pointer is guaranteed to be filled, so add it to the checksum computation
-        checksum += (left_pointer as u128) * (storage[left_pointer] as u128);
-
-        // The left pointer is advanced in every iteration
+        // The left block is free and the right block is occupied, so swap them
+        storage[left_pointer] = storage[right_pointer];
+        storage[right_pointer] = -1;
         left_pointer += 1;
+        right_pointer -= 1;
+        moved_blocks += 1;
     }
 
-    println!("Computed {} as checksum", checksum);
-    checksum
+    moved_blocks
 }
 
-pub fn part_2(path: &str) -> u128 {
-    let mut storage = preprocess(path);
+/**
+   A single whole file move, as performed by the part 2 compaction
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileMove {
+    pub id: i32,
+    pub from: usize,
+    pub to: usize,
+    pub size: usize,
+}
 
+/**
+   Moves whole files from the end of the storage into the first free span on their left that
+   is large enough to hold them. The storage is modified in place. After every move `on_move`
+   is called with the move and the storage as it looks after the move.
+ */
+fn compact_whole_files(storage: &mut [i32], mut on_move: impl FnMut(&FileMove, &[i32])) {
     let mut pointer = storage.len() as isize;
     let mut block_id: i32 = -1;
 
     while pointer > 0 {
         // Get the block size and block id
-        while block_id < 0 && pointer >= 0 {
+        while block_id < 0 && pointer > 0 {
             pointer -= 1;
             block_id = storage[pointer as usize];
         }
 
+        // The scan reached the start of the disk without finding another file
+        if block_id < 0 {
+            break;
+        }
+
         let block_start = pointer;
         let mut next_block_id = block_id;
 
@@ -113,7 +231,7 @@ pub fn part_2(path: &str) -> u128 {
             // We need to start a new free block count
             if free_block_start < 0 {
                 free_block_size = 0;
-                free_block_start = i as isize;
+                free_block_start = i;
             }
 
             free_block_size += 1;
@@ -122,15 +240,22 @@ pub fn part_2(path: &str) -> u128 {
             // if the found filled block from the end fits into
             // the last free block
             if block_size <= free_block_size {
-                let mut f = free_block_start as usize;
+                let to = free_block_start as usize;
 
                 // The block end must be exclusive
-                for b in block_end+1..=block_start {
+                for (f, b) in (to..).zip(block_end+1..=block_start) {
                     storage[f] = storage[b as usize];
                     storage[b as usize] = -1;
-                    f += 1;
                 }
 
+                let file_move = FileMove {
+                    id: block_id,
+                    from: (block_end + 1) as usize,
+                    to,
+                    size: block_size as usize,
+                };
+                on_move(&file_move, storage);
+
                 // We break the loop because we successfully moved the block
                 break;
             }
@@ -138,19 +263,392 @@ pub fn part_2(path: &str) -> u128 {
 
         block_id = next_block_id;
     }
+}
 
-    let mut checksum = 0;
+/**
+   Computes the checksum of the storage, which is the sum of each block position multiplied by
+   the id of the file stored in it. Free blocks are skipped.
+ */
+fn compute_checksum(storage: &[i32]) -> u128 {
+    storage.iter()
+        .enumerate()
+        .filter(|(_, id)| **id >= 0)
+        .map(|(position, id)| (position as u128) * (*id as u128))
+        .sum()
+}
+
+/**
+   Returns all files of the storage as (id, start, size) tuples, ordered by their position
+ */
+fn files_of(storage: &[i32]) -> Vec<(i32, usize, usize)> {
+    let mut files: Vec<(i32, usize, usize)> = vec![];
+    let mut i = 0;
+
+    while i < storage.len() {
+        let id = storage[i];
+        let size = storage[i..].iter().take_while(|other| **other == id).count();
+
+        if id >= 0 {
+            files.push((id, i, size));
+        }
+
+        i += size;
+    }
+
+    files
+}
+
+/**
+   Returns all free spans of the storage as (start, size) tuples, ordered by their position
+ */
+fn free_spans_of(storage: &[i32]) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = vec![];
+    let mut i = 0;
+
+    while i < storage.len() {
+        let size = storage[i..].iter().take_while(|id| **id == storage[i]).count();
+
+        if storage[i] < 0 {
+            spans.push((i, size));
+        }
+
+        i += size;
+    }
+
+    spans
+}
+
+/**
+   Inserts a newly freed span into the ordered list of free spans, merging it with
+   directly adjacent spans
+ */
+fn release_span(free_spans: &mut Vec<(usize, usize)>, start: usize, size: usize) {
+    let mut index = free_spans.partition_point(|span| span.0 < start);
+    free_spans.insert(index, (start, size));
+
+    // Merge with the following span
+    if index + 1 < free_spans.len() && free_spans[index].0 + free_spans[index].1 == free_spans[index + 1].0 {
+        free_spans[index].1 += free_spans[index + 1].1;
+        free_spans.remove(index + 1);
+    }
+
+    // Merge with the preceding span
+    if index > 0 && free_spans[index - 1].0 + free_spans[index - 1].1 == free_spans[index].0 {
+        free_spans[index - 1].1 += free_spans[index].1;
+        free_spans.remove(index);
+        index -= 1;
+    }
+
+    debug_assert!(free_spans[index].1 > 0);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Fit {
+    First,
+    Best,
+    Worst,
+}
+
+/**
+   Moves every file at most once into a free span on its left. The files are processed in
+   descending or ascending id order, and the fit decides which of the large enough spans
+   is used. Returns the number of moved files and the number of moved blocks.
+ */
+fn compact_files_with_fit(storage: &mut [i32], ascending: bool, fit: Fit) -> (usize, usize) {
+    let mut files = files_of(storage);
+    let mut free_spans = free_spans_of(storage);
+    let mut moves = 0;
+    let mut bytes_moved = 0;
+
+    files.sort_by_key(|file| file.0);
+    if !ascending {
+        files.reverse();
+    }
+
+    for (id, start, size) in files {
+        let candidates = free_spans.iter()
+            .enumerate()
+            .take_while(|(_, span)| span.0 < start)
+            .filter(|(_, span)| span.1 >= size);
 
-    for i in 0..storage.len() {
-        if storage[i] >= 0 {
-            checksum += (i as u128) * (storage[i] as u128);
+        // Ties are always resolved in favor of the leftmost span
+        let chosen = match fit {
+            Fit::First => candidates.map(|(index, _)| index).next(),
+            Fit::Best => candidates.min_by_key(|(index, span)| (span.1, *index)).map(|(index, _)| index),
+            Fit::Worst => candidates.min_by_key(|(index, span)| (usize::MAX - span.1, *index)).map(|(index, _)| index),
+        };
+
+        let Some(index) = chosen else {
+            continue;
+        };
+
+        let to = free_spans[index].0;
+        storage[to..to + size].fill(id);
+        storage[start..start + size].fill(-1);
+
+        free_spans[index].0 += size;
+        free_spans[index].1 -= size;
+        if free_spans[index].1 == 0 {
+            free_spans.remove(index);
         }
+        release_span(&mut free_spans, start, size);
+
+        moves += 1;
+        bytes_moved += size;
+    }
+
+    (moves, bytes_moved)
+}
+
+/**
+   Packs all files to the left while keeping their order, so that no free block is left
+   between two files. Returns the number of moved files and the number of moved blocks.
+ */
+fn defragment(storage: &mut [i32]) -> (usize, usize) {
+    let mut moves = 0;
+    let mut bytes_moved = 0;
+    let mut target = 0;
+
+    for (id, start, size) in files_of(storage) {
+        if start != target {
+            // The target is always left of the start, so the copy never overwrites
+            // blocks of the file that were not copied yet
+            for offset in 0..size {
+                storage[start + offset] = -1;
+                storage[target + offset] = id;
+            }
+
+            moves += 1;
+            bytes_moved += size;
+        }
+
+        target += size;
+    }
+
+    (moves, bytes_moved)
+}
+
+/**
+   Returns the share of free blocks that lie in front of the last filled block. A value of 0
+   means that all free space is at the end of the disk, a value of 1 means that none of it is.
+ */
+fn fragmentation_of(storage: &[i32]) -> f64 {
+    let free_blocks = storage.iter().filter(|id| **id < 0).count();
+    if free_blocks == 0 {
+        return 0.0;
     }
 
+    let last_filled = storage.iter().rposition(|id| *id >= 0).unwrap_or(0);
+    let gaps = storage[..last_filled].iter().filter(|id| **id < 0).count();
+
+    gaps as f64 / free_blocks as f64
+}
+
+/**
+   The available policies to compact the disk
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CompactionStrategy {
+    /// Moves single blocks from the end into the leftmost free block (part 1)
+    Blocks,
+    /// Moves whole files in descending id order into the leftmost free span (part 2)
+    FirstFit,
+    /// Moves whole files in descending id order into the smallest free span that fits
+    BestFit,
+    /// Moves whole files in descending id order into the largest free span
+    WorstFit,
+    /// Moves whole files in ascending id order into the leftmost free span
+    AscendingFirstFit,
+    /// Packs all files to the left without leaving any gaps, keeping their order
+    FullDefrag,
+}
+
+pub const COMPACTION_STRATEGIES: [CompactionStrategy; 6] = [
+    CompactionStrategy::Blocks,
+    CompactionStrategy::FirstFit,
+    CompactionStrategy::BestFit,
+    CompactionStrategy::WorstFit,
+    CompactionStrategy::AscendingFirstFit,
+    CompactionStrategy::FullDefrag,
+];
+
+/**
+   Summarizes the result of a single compaction run
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CompactionReport {
+    pub checksum: u128,
+    /// Number of move operations. For the block strategy every block counts as a move
+    pub moves: usize,
+    pub bytes_moved: usize,
+    /// See `fragmentation_of`
+    pub fragmentation: f64,
+}
+
+impl CompactionStrategy {
+    /**
+       Compacts the storage in place and reports what was done
+     */
+    pub fn compact(&self, storage: &mut [i32]) -> CompactionReport {
+        let (moves, bytes_moved) = match self {
+            Self::Blocks => {
+                let moved_blocks = compact_blocks(storage);
+                (moved_blocks, moved_blocks)
+            },
+            Self::FirstFit => {
+                let mut moves = 0;
+                let mut bytes_moved = 0;
+                compact_whole_files(storage, |file_move, _| {
+                    moves += 1;
+                    bytes_moved += file_move.size;
+                });
+                (moves, bytes_moved)
+            },
+            Self::BestFit => compact_files_with_fit(storage, false, Fit::Best),
+            Self::WorstFit => compact_files_with_fit(storage, false, Fit::Worst),
+            Self::AscendingFirstFit => compact_files_with_fit(storage, true, Fit::First),
+            Self::FullDefrag => defragment(storage),
+        };
+
+        CompactionReport {
+            checksum: compute_checksum(storage),
+            moves,
+            bytes_moved,
+            fragmentation: fragmentation_of(storage),
+        }
+    }
+}
+
+/**
+   Compacts the disk of the given input with the given strategy, and returns the compacted
+   layout as a dense disk map. The ids are kept if possible, otherwise they are renumbered.
+ */
+pub fn compacted_disk_map(path: &str, strategy: CompactionStrategy) -> String {
+    let mut storage = preprocess(path);
+    strategy.compact(&mut storage);
+
+    serialize_disk_map(&storage).unwrap_or_else(|| serialize_disk_map_renumbered(&storage))
+}
+
+/**
+   Compacts the disk of the given input with every available strategy, so that the
+   strategies can be compared on the same input
+ */
+pub fn compare_strategies(path: &str) -> Vec<(CompactionStrategy, CompactionReport)> {
+    let storage = preprocess(path);
+
+    COMPACTION_STRATEGIES.iter()
+        .map(|strategy| {
+            let mut strategy_storage = storage.clone();
+            (*strategy, strategy.compact(&mut strategy_storage))
+        })
+        .collect()
+}
+
+/**
+   Moves the blocks around with the given rules and then returns the new computed checksum
+ */
+pub fn part_1(path: &str) -> u128 {
+    let mut storage = preprocess(path);
+    compact_blocks(&mut storage);
+
+    let checksum = compute_checksum(&storage);
     println!("Computed {} as checksum", checksum);
     checksum
 }
 
+pub fn part_2(path: &str) -> u128 {
+    let mut storage = preprocess(path);
+    compact_whole_files(&mut storage, |_, _| {});
+
+    let checksum = compute_checksum(&storage);
+    println!("Computed {} as checksum", checksum);
+    checksum
+}
+
+/**
+   Returns the character a single block is rendered with. Free blocks are rendered as '.',
+   filled blocks as the last digit of their file id, just like in the puzzle text.
+ */
+fn block_symbol(id: i32) -> char {
+    if id < 0 {
+        return '.';
+    }
+
+    char::from_digit((id % 10) as u32, 10).expect("Should be a digit in base 10")
+}
+
+/**
+   Renders the storage in the puzzle notation, e.g. `00...111...2...333.44.5555.6666.777.888899`.
+   Since every block is a single character, file ids above 9 are shortened to their last digit.
+ */
+pub fn render_disk(storage: &[i32]) -> String {
+    storage.iter().map(|id| block_symbol(*id)).collect()
+}
+
+/**
+   Renders the storage as a run length encoded list, which is a lot shorter for large disks.
+   Each run of equal blocks is rendered as `<id>x<length>`, free runs as `.x<length>`, e.g.
+   `0x2 .x3 1x3`. Unlike `render_disk` the full file ids are kept.
+ */
+pub fn render_disk_compressed(storage: &[i32]) -> String {
+    let mut runs: Vec<String> = vec![];
+    let mut i = 0;
+
+    while i < storage.len() {
+        let id = storage[i];
+        let run_length = storage[i..].iter().take_while(|other| **other == id).count();
+
+        if id < 0 {
+            runs.push(format!(".x{}", run_length));
+        } else {
+            runs.push(format!("{}x{}", id, run_length));
+        }
+
+        i += run_length;
+    }
+
+    runs.join(" ")
+}
+
+fn render(storage: &[i32], compressed: bool) -> String {
+    if compressed {
+        render_disk_compressed(storage)
+    } else {
+        render_disk(storage)
+    }
+}
+
+/**
+   Renders the disk of the given input before and after the part 1 compaction, one layout per line
+ */
+pub fn render_part_1(path: &str, compressed: bool) -> String {
+    let mut storage = preprocess(path);
+    let before = render(&storage, compressed);
+    compact_blocks(&mut storage);
+    let after = render(&storage, compressed);
+
+    format!("{}\n{}\n", before, after)
+}
+
+/**
+   Renders the disk of the given input before the part 2 compaction, followed by the layout
+   after every single file move. Each move is prefixed with a line describing it.
+ */
+pub fn render_part_2_trace(path: &str, compressed: bool) -> String {
+    let mut storage = preprocess(path);
+    let mut trace = format!("{}\n", render(&storage, compressed));
+
+    compact_whole_files(&mut storage, |file_move, storage| {
+        trace.push_str(&format!(
+            "Moved file {} ({} blocks) from {} to {}\n{}\n",
+            file_move.id, file_move.size, file_move.from, file_move.to, render(storage, compressed),
+        ));
+    });
+
+    trace
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -191,4 +689,157 @@ mod test {
         let checksum = part_2("./inputs/day_09_test.txt");
         assert_eq!(checksum, 2858);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_render_disk() {
+        let storage = preprocess("./inputs/day_09_test.txt");
+        assert_eq!(render_disk(&storage), "00...111...2...333.44.5555.6666.777.888899");
+        assert_eq!(render_disk(&[10, 11, -1, 12]), "01.2");
+    }
+
+    #[test]
+    fn test_render_disk_compressed() {
+        assert_eq!(render_disk_compressed(&[0, 0, -1, -1, -1, 12, 12, 12]), "0x2 .x3 12x3");
+        assert_eq!(render_disk_compressed(&[]), "");
+    }
+
+    #[test]
+    fn test_render_part_1() {
+        assert_eq!(render_part_1("./inputs/day_09_test.txt", false), concat!(
+            "00...111...2...333.44.5555.6666.777.888899\n",
+            "0099811188827773336446555566..............\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_part_2_trace() {
+        assert_eq!(render_part_2_trace("./inputs/day_09_test.txt", false), concat!(
+            "00...111...2...333.44.5555.6666.777.888899\n",
+            "Moved file 9 (2 blocks) from 40 to 2\n",
+            "0099.111...2...333.44.5555.6666.777.8888..\n",
+            "Moved file 7 (3 blocks) from 32 to 8\n",
+            "0099.1117772...333.44.5555.6666.....8888..\n",
+            "Moved file 4 (2 blocks) from 19 to 12\n",
+            "0099.111777244.333....5555.6666.....8888..\n",
+            "Moved file 2 (1 blocks) from 11 to 4\n",
+            "00992111777.44.333....5555.6666.....8888..\n",
+        ));
+        assert_eq!(render_part_2_trace("./inputs/day_09_test_empty_file_1.txt", false), ".\n");
+        assert_eq!(render_part_2_trace("./inputs/day_09_test_empty_file_2.txt", false), "..\n");
+    }
+
+    #[test]
+    fn test_release_span() {
+        let mut spans = vec![(0, 2), (6, 1)];
+        release_span(&mut spans, 2, 2);
+        assert_eq!(spans, vec![(0, 4), (6, 1)]);
+        release_span(&mut spans, 4, 2);
+        assert_eq!(spans, vec![(0, 7)]);
+        release_span(&mut spans, 9, 1);
+        assert_eq!(spans, vec![(0, 7), (9, 1)]);
+    }
+
+    #[test]
+    fn test_compaction_strategies() {
+        let reports = compare_strategies("./inputs/day_09_test.txt");
+        let report_of = |strategy| reports.iter().find(|(s, _)| *s == strategy).unwrap().1;
+
+        assert_eq!(report_of(CompactionStrategy::Blocks).checksum, 1928);
+        assert_eq!(report_of(CompactionStrategy::Blocks).fragmentation, 0.0);
+        assert_eq!(report_of(CompactionStrategy::FirstFit).checksum, 2858);
+        assert_eq!(report_of(CompactionStrategy::FirstFit).moves, 4);
+        assert_eq!(report_of(CompactionStrategy::FirstFit).bytes_moved, 8);
+        assert_eq!(report_of(CompactionStrategy::FullDefrag).fragmentation, 0.0);
+
+        for path in ["./inputs/day_09_test_empty_file_1.txt", "./inputs/day_09_test_empty_file_2.txt"] {
+            for (strategy, report) in compare_strategies(path) {
+                assert_eq!((report.checksum, report.moves, report.bytes_moved), (0, 0, 0), "{:?}", strategy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit_strategies() {
+        // Free spans of size 3, 1 and 2 in front of a file of size 1
+        let storage = vec![0, -1, -1, -1, 1, -1, 2, -1, -1, 3];
+
+        let mut best = storage.clone();
+        CompactionStrategy::BestFit.compact(&mut best);
+        assert_eq!(render_disk(&best), "021..3....");
+
+        let mut worst = storage.clone();
+        CompactionStrategy::WorstFit.compact(&mut worst);
+        assert_eq!(render_disk(&worst), "0321......");
+
+        let mut ascending = storage.clone();
+        CompactionStrategy::AscendingFirstFit.compact(&mut ascending);
+        assert_eq!(render_disk(&ascending), "0123......");
+
+        let mut defrag = storage.clone();
+        let report = CompactionStrategy::FullDefrag.compact(&mut defrag);
+        assert_eq!(render_disk(&defrag), "0123......");
+        assert_eq!((report.moves, report.bytes_moved), (3, 3));
+    }
+
+    #[test]
+    fn test_parse_disk_map_trailing_newline() {
+        assert_eq!(parse_disk_map("12345\n"), parse_disk_map("12345"));
+        assert_eq!(render_disk(&parse_disk_map("12345\n")), "0..111....22222");
+    }
+
+    #[test]
+    fn test_serialize_disk_map_round_trip() {
+        let disk_maps = [
+            "2333133121414131402",
+            "12345",
+            "0312",
+            "10002",
+            "1000200",
+            "90909",
+            "",
+        ];
+
+        for disk_map in disk_maps {
+            let storage = parse_disk_map(disk_map);
+            let serialized = serialize_disk_map(&storage).unwrap();
+            assert_eq!(parse_disk_map(&serialized), storage, "{}", disk_map);
+        }
+
+        let storage = preprocess("./inputs/day_09.txt");
+        assert_eq!(parse_disk_map(&serialize_disk_map(&storage).unwrap()), storage);
+    }
+
+    #[test]
+    fn test_serialize_disk_map() {
+        assert_eq!(serialize_disk_map(&parse_disk_map("2333133121414131402")).unwrap(), "2333133121414131402");
+        assert_eq!(serialize_disk_map(&[-1, -1, 1, -1]).unwrap(), "0211");
+        assert_eq!(serialize_disk_map(&[-1, -1, 0, -1]), None);
+        assert_eq!(serialize_disk_map(&[1, 0]), None);
+        assert_eq!(serialize_disk_map(&[0; 10]), None);
+        let mut long_gap = vec![-1; 12];
+        long_gap[0] = 0;
+        long_gap[11] = 1;
+        assert_eq!(serialize_disk_map(&long_gap), None);
+        long_gap[11] = 2;
+        assert_eq!(serialize_disk_map(&long_gap).unwrap(), "19011");
+    }
+
+    #[test]
+    fn test_serialize_disk_map_renumbered() {
+        let mut storage = preprocess("./inputs/day_09_test.txt");
+        CompactionStrategy::FirstFit.compact(&mut storage);
+        let serialized = serialize_disk_map_renumbered(&storage);
+
+        assert_eq!(render_disk(&parse_disk_map(&serialized)), "00112333444.55.666....7777.8888.....9999..");
+        assert_eq!(serialize_disk_map_renumbered(&[3; 12]), "903");
+
+        let long_gap = [0, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, 1];
+        assert_eq!(parse_disk_map(&serialize_disk_map_renumbered(&long_gap)).len(), long_gap.len());
+    }
+
+    #[test]
+    fn test_compacted_disk_map() {
+        let disk_map = compacted_disk_map("./inputs/day_09_test.txt", CompactionStrategy::FullDefrag);
+        assert_eq!(render_disk(&parse_disk_map(&disk_map)), "0011123334455556666777888899..............");
+    }
+}
@@ -1,112 +1,318 @@
+use std::collections::{BTreeMap, HashMap};
+
+/**
+   Marks cells that can't be stepped on, like the '.' cells of some example maps
+ */
+pub const IMPASSABLE: u32 = u32::MAX;
+
 /**
-   Converts the 2D playing field into a 2D array
+   Converts the 2D playing field into a 2D array. Cells that aren't digits become `IMPASSABLE`.
  */
 fn preprocess(path: &str) -> Vec<Vec<u32>> {
     let contents = std::fs::read_to_string(path).expect(path);
     
     contents.lines()
         .map(|line| line.chars()
-            .map(|c| c.to_digit(10).expect("Expected parsable u32"))
+            .map(|c| c.to_digit(10).unwrap_or(IMPASSABLE))
             .collect())
         .collect()
 }
 
-fn position_in_bounds(position: (isize, isize), map: &Vec<Vec<u32>>) -> bool {
-    let max_x = map[0].len() as isize;
-    let max_y = map.len() as isize;
-    position.0 < max_x && position.0 >= 0 && position.1 < max_y && position.1 >= 0
+/**
+   Returns true if the map has a cell at the given position. Rows may have different lengths.
+ */
+fn position_in_bounds(position: (isize, isize), map: &[Vec<u32>]) -> bool {
+    let (Ok(x), Ok(y)) = (usize::try_from(position.0), usize::try_from(position.1)) else {
+        return false;
+    };
+    map.get(y).and_then(|row| row.get(x)).is_some()
 }
 
-fn get_trail_ends_of(x: isize, y: isize, map: &Vec<Vec<u32>>) -> Vec<(isize, isize)> {
-    let positions_to_check = vec![(x-1, y), (x, y-1), (x+1, y), (x, y+1)];
-    let current_value = map[y as usize][x as usize];
+/**
+   The rules of a walk over the map. A step goes to a neighboring cell whose height differs
+   from the current one by a delta inside of `min_delta..=max_delta`. A trail starts on a cell
+   with `start_height` and ends as soon as it reaches a cell with `target_height`.
+   The default is the puzzle walk: steps go up by exactly one from 0 to 9 in 4 directions.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalkConfig {
+    pub min_delta: i64,
+    pub max_delta: i64,
+    /// Whether the diagonal neighbors can be stepped on as well
+    pub diagonal: bool,
+    pub start_height: u32,
+    pub target_height: u32,
+}
 
-    if current_value == 9 {
-        return vec![(x, y)];
+impl Default for WalkConfig {
+    fn default() -> Self {
+        WalkConfig { min_delta: 1, max_delta: 1, diagonal: false, start_height: 0, target_height: 9 }
     }
+}
 
-    let mut trail_ends: Vec<(isize, isize)> = vec![];
-    
-    for position in positions_to_check {
-        if !position_in_bounds(position, map) {
-            continue;
+impl WalkConfig {
+    /**
+       Returns true if a step from the `from` height to the `to` height is allowed
+     */
+    fn allows_step(&self, from: u32, to: u32) -> bool {
+        if from == IMPASSABLE || to == IMPASSABLE {
+            return false;
         }
 
-        if map[position.1 as usize][position.0 as usize] == current_value + 1 {
-            trail_ends.append(&mut get_trail_ends_of(position.0, position.1, map));
-        }
+        let delta = to as i64 - from as i64;
+        delta >= self.min_delta && delta <= self.max_delta
     }
 
-    trail_ends.sort();
-    trail_ends.dedup();
+    /**
+       Panics if the rules would allow trails that walk in circles. Every step must either go
+       up or go down, since otherwise there could be infinitely many trails.
+     */
+    fn assert_valid(&self) {
+        assert!(self.min_delta <= self.max_delta, "The delta range {:?} is empty", self.min_delta..=self.max_delta);
+        assert!(self.min_delta > 0 || self.max_delta < 0,
+            "The delta range {:?} must be strictly ascending or strictly descending", self.min_delta..=self.max_delta);
+    }
+}
+
+/**
+   Result of the dynamic programming pass over a map. The peak sets are only kept for the
+   cells of the start height, since keeping them for every cell would need too much memory
+   on large maps.
+ */
+struct TrailTable {
+    /// Number of distinct trails from each cell to any cell of the target height
+    ratings: Vec<Vec<u64>>,
+    /// Sorted (x, y) positions of all reachable target cells for each cell of the start height
+    peaks: HashMap<(usize, usize), Vec<(usize, usize)>>,
+}
+
+/**
+   Returns the positions of the in bound 4-neighbors, or 8-neighbors if `diagonal` is set,
+   of the given position
+ */
+fn neighbors_of(x: usize, y: usize, diagonal: bool, map: &[Vec<u32>]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let (x, y) = (x as isize, y as isize);
+    let straight = [(x-1, y), (x, y-1), (x+1, y), (x, y+1)];
+    let diagonals = [(x-1, y-1), (x+1, y-1), (x+1, y+1), (x-1, y+1)];
 
-    trail_ends
+    straight.into_iter()
+        .chain(diagonals.into_iter().filter(move |_| diagonal))
+        .filter(|position| position_in_bounds(*position, map))
+        .map(|(x, y)| (x as usize, y as usize))
 }
 
 /**
-   Returns the number of distinct paths that lead to a 9 from the given x,y position
+   Computes the trail ratings for every cell and, if requested, the reachable target cells for
+   every cell of the start height in a single pass. Since every step goes strictly up (or
+   strictly down), the results for all cells of one height only depend on the cells with a
+   higher (or lower) height. The heights are therefore processed from the top down (or from
+   the bottom up), and every cell is visited exactly once.
  */
-fn get_number_of_distinct_paths_of(x: isize, y: isize, map: &Vec<Vec<u32>>) -> u32 {
-    let positions_to_check = vec![(x-1, y), (x, y-1), (x+1, y), (x, y+1)];
-    let current_value = map[y as usize][x as usize];
+fn compute_trail_table(map: &[Vec<u32>], config: &WalkConfig, keep_peaks: bool) -> TrailTable {
+    config.assert_valid();
 
-    if current_value == 9 {
-        return 1;
+    let mut cells_by_height: BTreeMap<u32, Vec<(usize, usize)>> = BTreeMap::new();
+    for (y, row) in map.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            if *value != IMPASSABLE {
+                cells_by_height.entry(*value).or_default().push((x, y));
+            }
+        }
     }
 
-    let mut sum = 0;
-    
-    for position in positions_to_check {
-        if !position_in_bounds(position, map) {
-            continue;
+    // Climbing trails depend on the higher cells, so those need to be processed first
+    let mut levels: Vec<(u32, Vec<(usize, usize)>)> = cells_by_height.into_iter().collect();
+    if config.min_delta > 0 {
+        levels.reverse();
+    }
+
+    // The index of each cell inside of its height level, so that the peak sets of a level
+    // can be stored in a plain vector
+    let mut level_index: Vec<Vec<usize>> = map.iter().map(|row| vec![0; row.len()]).collect();
+    for (_, cells) in &levels {
+        for (index, &(x, y)) in cells.iter().enumerate() {
+            level_index[y][x] = index;
+        }
+    }
+
+    // A cell only depends on the levels at most this far away from its own height
+    let window = config.min_delta.abs().max(config.max_delta.abs());
+
+    let mut ratings: Vec<Vec<u64>> = map.iter().map(|row| vec![0; row.len()]).collect();
+    let mut peaks_by_height: HashMap<u32, Vec<Vec<(usize, usize)>>> = HashMap::new();
+
+    for (height, cells) in &levels {
+        let height = *height;
+        let mut level_peaks: Vec<Vec<(usize, usize)>> = vec![];
+
+        for &(x, y) in cells {
+            if height == config.target_height {
+                ratings[y][x] = 1;
+                if keep_peaks {
+                    level_peaks.push(vec![(x, y)]);
+                }
+                continue;
+            }
+
+            let mut rating = 0;
+            let mut reachable: Vec<(usize, usize)> = vec![];
+
+            for (n_x, n_y) in neighbors_of(x, y, config.diagonal, map) {
+                let neighbor_height = map[n_y][n_x];
+                if config.allows_step(height, neighbor_height) {
+                    rating += ratings[n_y][n_x];
+                    if keep_peaks {
+                        reachable.extend_from_slice(&peaks_by_height[&neighbor_height][level_index[n_y][n_x]]);
+                    }
+                }
+            }
+
+            ratings[y][x] = rating;
+            if keep_peaks {
+                reachable.sort_unstable();
+                reachable.dedup();
+                level_peaks.push(reachable);
+            }
         }
 
-        if map[position.1 as usize][position.0 as usize] == current_value + 1 {
-            sum += get_number_of_distinct_paths_of(position.0, position.1, map);
+        if keep_peaks {
+            peaks_by_height.insert(height, level_peaks);
+            // Levels outside of the window aren't needed anymore, except for the start height
+            peaks_by_height.retain(|other, _| {
+                *other == config.start_height || (*other as i64 - height as i64).abs() <= window
+            });
         }
     }
 
-    sum
+    let start_cells = levels.iter()
+        .find(|(height, _)| *height == config.start_height)
+        .map_or(&[][..], |(_, cells)| &cells[..]);
+    let peaks = start_cells.iter()
+        .copied()
+        .zip(peaks_by_height.remove(&config.start_height).unwrap_or_default())
+        .collect();
+
+    TrailTable { ratings, peaks }
 }
 
-pub fn part_1(path: &str) -> u32 {
-    let map = preprocess(path);
+/**
+   Everything that is known about a single trailhead
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrailheadReport {
+    /// The (x, y) position of the trailhead
+    pub position: (usize, usize),
+    /// The number of distinct target cells that can be reached
+    pub score: usize,
+    /// The number of distinct trails to any target cell
+    pub rating: u64,
+    /// The sorted (x, y) positions of the reachable target cells
+    pub peaks: Vec<(usize, usize)>,
+}
 
-    let mut sum = 0;
+/**
+   Returns the reports of all trailheads of the map, ordered by row and then by column
+ */
+fn trailhead_reports_of(map: &[Vec<u32>], config: &WalkConfig) -> Vec<TrailheadReport> {
+    let mut table = compute_trail_table(map, config, true);
+    let mut reports: Vec<TrailheadReport> = vec![];
 
-    for y in 0..map.len() {
-        let row = &map[y];
-        for x in 0..row.len() {
-            let value = row[x];
-            if value == 0 {
-                let trail_ends = get_trail_ends_of(x as isize, y as isize, &map);
-                sum += trail_ends.len();
-                println!("Found trail_ends {:?} for {},{}", trail_ends, x, y);
+    for (y, row) in map.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            if *value == config.start_height {
+                let peaks = table.peaks.remove(&(x, y)).unwrap_or_default();
+                reports.push(TrailheadReport {
+                    position: (x, y),
+                    score: peaks.len(),
+                    rating: table.ratings[y][x],
+                    peaks,
+                });
             }
         }
     }
 
+    reports
+}
+
+/**
+   Returns the reports of all trailheads of the given input, ordered by row and then by column
+ */
+pub fn trailhead_reports(path: &str, config: &WalkConfig) -> Vec<TrailheadReport> {
+    let map = preprocess(path);
+    trailhead_reports_of(&map, config)
+}
+
+/**
+   Lazily yields every distinct trail from the given (x, y) position to any target cell, as the
+   list of positions along the trail. Cells without any trail to a target cell are never
+   entered, so every explored branch leads to at least one yielded trail.
+ */
+fn trails_from<'a>(map: &'a [Vec<u32>], config: &'a WalkConfig, start: (usize, usize)) -> impl Iterator<Item = Vec<(usize, usize)>> + 'a {
+    let ratings = compute_trail_table(map, config, false).ratings;
+    let mut stack: Vec<Vec<(usize, usize)>> = vec![];
+
+    if ratings.get(start.1).and_then(|row| row.get(start.0)).is_some_and(|rating| *rating > 0) {
+        stack.push(vec![start]);
+    }
+
+    std::iter::from_fn(move || {
+        while let Some(trail) = stack.pop() {
+            let (x, y) = *trail.last().expect("Trails are never empty");
+            let height = map[y][x];
+
+            if height == config.target_height {
+                return Some(trail);
+            }
+
+            // Pushed in reverse, so that the trails are yielded in neighbor order
+            let next_positions: Vec<(usize, usize)> = neighbors_of(x, y, config.diagonal, map)
+                .filter(|(n_x, n_y)| config.allows_step(height, map[*n_y][*n_x]) && ratings[*n_y][*n_x] > 0)
+                .collect();
+
+            for position in next_positions.into_iter().rev() {
+                let mut next_trail = trail.clone();
+                next_trail.push(position);
+                stack.push(next_trail);
+            }
+        }
+
+        None
+    })
+}
+
+/**
+   Returns up to `limit` distinct trails that start at the given (x, y) position of the given
+   input. Each trail is the list of (x, y) positions from the start to a target cell.
+ */
+pub fn enumerate_trails(path: &str, config: &WalkConfig, start: (usize, usize), limit: usize) -> Vec<Vec<(usize, usize)>> {
+    let map = preprocess(path);
+    trails_from(&map, config, start).take(limit).collect()
+}
+
+pub fn part_1(path: &str) -> u32 {
+    let map = preprocess(path);
+    let sum: usize = trailhead_reports_of(&map, &WalkConfig::default()).iter().map(|report| report.score).sum();
+
     println!("Found {} trail ends", sum);
     sum as u32
 }
 
-pub fn part_2(path: &str) -> u32 {
+pub fn part_2(path: &str) -> u64 {
     let map = preprocess(path);
+    let table = compute_trail_table(&map, &WalkConfig::default(), false);
 
     let mut sum = 0;
 
-    for y in 0..map.len() {
-        let row = &map[y];
-        for x in 0..row.len() {
-            let value = row[x];
-            if value == 0 {
-                sum += get_number_of_distinct_paths_of(x as isize, y as isize, &map);
+    for (y, row) in map.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            if *value == 0 {
+                sum += table.ratings[y][x];
             }
         }
     }
 
     println!("The final sum is {}", sum);
-    sum as u32
+    sum
 }
 
 #[cfg(test)]
@@ -129,7 +335,7 @@ mod test {
             vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
         ];
 
-        assert_eq!(get_trail_ends_of(0, 0, &v), vec![(9, 0)]);
+        assert_eq!(compute_trail_table(&v, &WalkConfig::default(), true).peaks[&(0, 0)], vec![(9, 0)]);
     }
 
     #[test]
@@ -140,7 +346,7 @@ mod test {
             vec![2, 3, 4, 5, 6, 7, 8, 9, 1, 1],
         ];
 
-        assert_eq!(get_number_of_distinct_paths_of(0, 0, &v), 2);
+        assert_eq!(compute_trail_table(&v, &WalkConfig::default(), false).ratings[0][0], 2);
     }
 
     #[test]
@@ -154,7 +360,7 @@ mod test {
             vec![5, 6, 7, 8, 9, 0],
         ];
 
-        assert_eq!(get_number_of_distinct_paths_of(0, 0, &v), 227);
+        assert_eq!(compute_trail_table(&v, &WalkConfig::default(), false).ratings[0][0], 227);
     }
 
     #[test]
@@ -166,4 +372,118 @@ mod test {
     fn test_part_2() {
         assert_eq!(part_2("./inputs/day_10_test.txt"), 81);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_compute_trail_table_large_map() {
+        // A diagonal terrain, where every 0 has many trails to the 9s in front of it
+        let size = 1000;
+        let map: Vec<Vec<u32>> = (0..size)
+            .map(|y| (0..size).map(|x| ((x + y) % 10) as u32).collect())
+            .collect();
+        let table = compute_trail_table(&map, &WalkConfig::default(), true);
+
+        // From (0, 0) all 9s on the diagonal x + y = 9 can be reached, along 2^9 paths
+        assert_eq!(table.peaks[&(0, 0)].len(), 10);
+        assert_eq!(table.ratings[0][0], 512);
+        assert_eq!(table.ratings[size - 1][size - 1], 0);
+    }
+
+    #[test]
+    fn test_trailhead_reports() {
+        let reports = trailhead_reports("./inputs/day_10_test.txt", &WalkConfig::default());
+        let scores: Vec<usize> = reports.iter().map(|report| report.score).collect();
+        let ratings: Vec<u64> = reports.iter().map(|report| report.rating).collect();
+
+        assert_eq!(reports.len(), 9);
+        assert_eq!(reports[0].position, (2, 0));
+        assert_eq!(scores, vec![5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        assert_eq!(ratings, vec![20, 24, 10, 4, 1, 4, 5, 8, 5]);
+        assert_eq!(reports[4].peaks, vec![(4, 6)]);
+    }
+
+    #[test]
+    fn test_enumerate_trails() {
+        let trails = enumerate_trails("./inputs/day_10_test.txt", &WalkConfig::default(), (2, 0), 100);
+        assert_eq!(trails.len(), 20);
+        assert!(trails.iter().all(|trail| trail.len() == 10 && trail[0] == (2, 0)));
+
+        let mut unique_trails = trails.clone();
+        unique_trails.sort();
+        unique_trails.dedup();
+        assert_eq!(unique_trails.len(), 20);
+
+        assert_eq!(enumerate_trails("./inputs/day_10_test.txt", &WalkConfig::default(), (2, 0), 3).len(), 3);
+        assert_eq!(enumerate_trails("./inputs/day_10_test.txt", &WalkConfig::default(), (0, 0), 3), vec![vec![(0, 0), (1, 0)]]);
+        assert!(enumerate_trails("./inputs/day_10_test.txt", &WalkConfig::default(), (0, 7), 3).is_empty());
+    }
+
+    #[test]
+    fn test_preprocess_impassable() {
+        let map = preprocess("./inputs/day_10_test_impassable.txt");
+
+        assert_eq!(map[0], vec![IMPASSABLE, IMPASSABLE, 9, 0, IMPASSABLE, IMPASSABLE, 9]);
+        assert_eq!(trailhead_reports("./inputs/day_10_test_impassable.txt", &WalkConfig::default())[0].score, 4);
+    }
+
+    #[test]
+    fn test_walk_config() {
+        let v: Vec<Vec<u32>> = vec![
+            vec![0, 2, 4, 6, 8],
+            vec![1, 3, 5, 7, 9],
+        ];
+
+        // Climbing by one only works by zigzagging between the two rows
+        assert_eq!(compute_trail_table(&v, &WalkConfig::default(), false).ratings[0][0], 0);
+
+        let climb_by_two = WalkConfig { min_delta: 1, max_delta: 2, ..WalkConfig::default() };
+        let table = compute_trail_table(&v, &climb_by_two, true);
+        assert_eq!(table.peaks[&(0, 0)], vec![(4, 1)]);
+        assert_eq!(table.ratings[0][0], 5);
+
+        let diagonal = WalkConfig { diagonal: true, ..WalkConfig::default() };
+        assert_eq!(compute_trail_table(&v, &diagonal, false).ratings[0][0], 1);
+
+        let descend = WalkConfig { min_delta: -2, max_delta: -1, start_height: 9, target_height: 0, ..WalkConfig::default() };
+        let table = compute_trail_table(&v, &descend, true);
+        assert_eq!(table.peaks[&(4, 1)], vec![(0, 0)]);
+        assert_eq!(table.ratings[1][4], 5);
+
+        let trails = trails_from(&v, &descend, (4, 1)).collect::<Vec<_>>();
+        assert_eq!(trails.len(), 5);
+        assert!(trails.iter().all(|trail| trail.last() == Some(&(0, 0))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_walk_config_with_cycles() {
+        let v: Vec<Vec<u32>> = vec![vec![0, 1]];
+        let config = WalkConfig { min_delta: -1, max_delta: 1, ..WalkConfig::default() };
+        compute_trail_table(&v, &config, false);
+    }
+
+    #[test]
+    fn test_ragged_map() {
+        // The rows have different lengths, and the peak at (7, 2) lies far beyond the first row
+        let map: Vec<Vec<u32>> = vec![
+            vec![0, 1],
+            vec![5, 2, 3, 4, 5, 6, 7],
+            vec![6, 7, 6, 5, 4, 3, 8, 9],
+        ];
+
+        assert!(position_in_bounds((7, 2), &map));
+        assert!(!position_in_bounds((2, 0), &map));
+        assert!(!position_in_bounds((7, 1), &map));
+
+        let reports = trailhead_reports_of(&map, &WalkConfig::default());
+        assert_eq!(reports, vec![TrailheadReport { position: (0, 0), score: 1, rating: 1, peaks: vec![(7, 2)] }]);
+
+        let trails: Vec<Vec<(usize, usize)>> = trails_from(&map, &WalkConfig::default(), (0, 0)).collect();
+        assert_eq!(trails, vec![vec![(0, 0), (1, 0), (1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (6, 2), (7, 2)]]);
+        assert_eq!(trails_from(&map, &WalkConfig::default(), (5, 0)).count(), 0);
+
+        // Going down from the peak beyond the first row's width reaches the trailhead
+        let descending = WalkConfig { min_delta: -1, max_delta: -1, start_height: 9, target_height: 0, ..WalkConfig::default() };
+        let reports = trailhead_reports_of(&map, &descending);
+        assert_eq!(reports, vec![TrailheadReport { position: (7, 2), score: 1, rating: 1, peaks: vec![(0, 0)] }]);
+    }
+}
@@ -1,5 +1,4 @@
-use std::collections::HashMap;
-use cached::proc_macro::cached;
+use std::collections::{HashMap, HashSet};
 
 /**
    Reads the stone configuration into a vector
@@ -14,87 +13,992 @@ fn preprocess(path: &str) -> Vec<u128> {
 }
 
 /**
-   Processes a single stone at the given index, and returns the stone(s) that resulted from the processing.
-   The resulting vector only resembles the change for the given index, not the entire vector!
-   Examples:
+   The most stones a single stone may split into
+ */
+pub const MAX_SPLIT_PARTS: usize = 8;
+
+/**
+   The stone(s) a single stone turns into during one blink. The common results of one or two
+   stones are stored inline, only splits into more parts need an allocation.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlinkResult {
+    One(u128),
+    Two(u128, u128),
+    /// For splits into more than two stones
+    Many(Box<[u128]>),
+}
+
+impl BlinkResult {
+    /**
+       Returns the resulting stones in order
+     */
+    pub fn stones(&self) -> impl Iterator<Item = u128> + '_ {
+        let (inline, len, many): ([u128; 2], usize, &[u128]) = match self {
+            BlinkResult::One(first) => ([*first, 0], 1, &[]),
+            BlinkResult::Two(first, second) => ([*first, *second], 2, &[]),
+            BlinkResult::Many(many) => ([0, 0], 0, many),
+        };
+
+        inline.into_iter().take(len).chain(many.iter().copied())
+    }
+}
+
+/**
+   Decides what a single stone turns into when blinking. Implement this to use completely custom
+   rules with a `StoneEngine`, or use a configured `RuleSet`.
+ */
+pub trait StoneRules {
+    /**
+       Returns the stone(s) the stone with the given value turns into, or None if a resulting
+       stone doesn't fit into a u128.
+     */
+    fn blink(&self, value: u128) -> Option<BlinkResult>;
+}
+
+/**
+   The configurable version of the puzzle rules, which are checked in this order:
+   1. A stone engraved with 0 is replaced by `zero_replacement`, unless that is None.
+   2. A stone whose number of digits in `base` is divisible by `split_parts` is split into that
+      many stones, each engraved with an equally long part of the digits.
+   3. Any other stone is multiplied by `multiplier`.
+
+   The default are the puzzle rules. The result only resembles the change for a single stone,
+   not for all stones! Examples:
 
    ```rs
-   process_stone(0) -> vec![1]
-   process_stone(23) -> vec![2, 3]
+   RuleSet::default().blink(0) -> Some(BlinkResult::One(1))
+   RuleSet::default().blink(23) -> Some(BlinkResult::Two(2, 3))
    ```
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RuleSet {
+    pub zero_replacement: Option<u128>,
+    pub base: u32,
+    pub split_parts: usize,
+    pub multiplier: u128,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet { zero_replacement: Some(1), base: 10, split_parts: 2, multiplier: 2024 }
+    }
+}
+
+impl StoneRules for RuleSet {
+    fn blink(&self, value: u128) -> Option<BlinkResult> {
+        assert!(self.base >= 2, "The base must be at least 2");
+        assert!((2..=MAX_SPLIT_PARTS).contains(&self.split_parts),
+            "A stone can only be split into 2 to {} parts", MAX_SPLIT_PARTS);
+
+        if value == 0 {
+            if let Some(replacement) = self.zero_replacement {
+                return Some(BlinkResult::One(replacement));
+            }
+        }
+
+        let base = self.base as u128;
+        let mut length = 1;
+        let mut rest = value / base;
+        while rest > 0 {
+            length += 1;
+            rest /= base;
+        }
+
+        // If the number of digits is divisible by the number of parts, split the digits up.
+        // The parts are cut off from the end, so the last one is computed first.
+        if length % self.split_parts == 0 {
+            let part_length = (length / self.split_parts) as u32;
+            // A part is always shorter than the whole number, so this can't overflow
+            let part_modulus = base.pow(part_length);
+            let mut parts = [0; MAX_SPLIT_PARTS];
+            let mut rest = value;
+
+            for part in parts[..self.split_parts].iter_mut().rev() {
+                *part = rest % part_modulus;
+                rest /= part_modulus;
+            }
 
-   This function uses caching functionality to prevent repeated computation for the same values
+            return Some(match self.split_parts {
+                2 => BlinkResult::Two(parts[0], parts[1]),
+                len => BlinkResult::Many(parts[..len].into()),
+            });
+        }
+
+        value.checked_mul(self.multiplier).map(BlinkResult::One)
+    }
+}
+
+/**
+   An unsigned integer of arbitrary size. Only supports what is needed to count stones, which
+   is adding counts up and printing them. The digits are stored in base 2^64, least significant
+   digit first, without trailing zero digits.
  */
-#[cached]
-fn process_stone(value: u128) -> Vec<u128> {    
-    if value == 0 {
-        return vec![1];
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigCount {
+    digits: Vec<u64>,
+}
+
+impl BigCount {
+    /**
+       Returns the value as u128, or None if it is too large
+     */
+    pub fn to_u128(&self) -> Option<u128> {
+        match self.digits.len() {
+            0 => Some(0),
+            1 => Some(self.digits[0] as u128),
+            2 => Some(((self.digits[1] as u128) << 64) | self.digits[0] as u128),
+            _ => None,
+        }
     }
-    
-    let value_as_string = value.to_string();
-    let length = value_as_string.len();
-    // We can be sure, that the len represents the actual number of digits,
-    // since it was created from a u128, which means all chars are ascii.
-    // If the number of digits is even, split them in two.
-    if length % 2 == 0 {
-        let half = length / 2;
-        let slice = value_as_string.as_str();
-        let first: u128 = slice[..half].parse().expect("Expected a parsable u128");
-        let second: u128 = slice[half..].parse().expect("Expected a parsable u128");
-        return vec![first, second];
+
+    /**
+       Divides the value by the given divisor in place, and returns the remainder
+     */
+    fn div_rem_in_place(&mut self, divisor: u64) -> u64 {
+        let mut remainder: u128 = 0;
+
+        for digit in self.digits.iter_mut().rev() {
+            let current = (remainder << 64) | *digit as u128;
+            *digit = (current / divisor as u128) as u64;
+            remainder = current % divisor as u128;
+        }
+
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+
+        remainder as u64
+    }
+}
+
+impl From<u128> for BigCount {
+    fn from(value: u128) -> Self {
+        let mut digits = vec![value as u64, (value >> 64) as u64];
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        BigCount { digits }
+    }
+}
+
+impl std::ops::AddAssign<&BigCount> for BigCount {
+    fn add_assign(&mut self, other: &BigCount) {
+        if self.digits.len() < other.digits.len() {
+            self.digits.resize(other.digits.len(), 0);
+        }
+
+        let mut carry = false;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            let other_digit = other.digits.get(i).copied().unwrap_or(0);
+            let (sum, first_carry) = digit.overflowing_add(other_digit);
+            let (sum, second_carry) = sum.overflowing_add(carry as u64);
+            *digit = sum;
+            carry = first_carry || second_carry;
+        }
+
+        if carry {
+            self.digits.push(1);
+        }
     }
+}
+
+impl std::fmt::Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Split off chunks of 19 decimal digits, which is the most that fits into a u64
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+        let mut rest = self.clone();
+        let mut chunks: Vec<u64> = vec![];
+
+        loop {
+            chunks.push(rest.div_rem_in_place(CHUNK));
+            if rest.digits.is_empty() {
+                break;
+            }
+        }
+
+        write!(f, "{}", chunks.pop().expect("There is always at least one chunk"))?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:019}", chunk)?;
+        }
 
-    vec![value * 2024]
+        Ok(())
+    }
 }
 
 /**
-   Steps through each stone in the stones count map and applies the rules. The result is the new
-   stones count map after a full iteration
+   A type that can count stones. Counts are only ever added up, so that is all it needs to do.
  */
-fn iterate(stones_count_map: &HashMap<u128, u128>) -> HashMap<u128, u128> {
-    let mut new_stones_count_map: HashMap<u128, u128> = HashMap::new();
-    
-    // For each unique stone engraving in the map, compute the resulting stone(s)
-    // Then insert the found stones into the result map with the original count.
-    // If there are five stones with engraving 2 in the original map, the same
-    // stone result will be contained five times after one iteration as well.
-    for (stone, count) in stones_count_map.into_iter() {
-        let new_stones = process_stone(*stone);
-        for new_stone in &new_stones {
-            if let Some(c) = new_stones_count_map.get(new_stone) {
+pub trait StoneCount: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+
+    /**
+       Adds the other count to this one. Returns false if the sum doesn't fit into the type.
+     */
+    fn checked_add_assign(&mut self, other: &Self) -> bool;
+}
+
+impl StoneCount for u128 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn checked_add_assign(&mut self, other: &Self) -> bool {
+        match self.checked_add(*other) {
+            Some(sum) => {
+                *self = sum;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+impl StoneCount for BigCount {
+    fn zero() -> Self {
+        BigCount::default()
+    }
+
+    fn one() -> Self {
+        BigCount::from(1)
+    }
+
+    fn checked_add_assign(&mut self, other: &Self) -> bool {
+        *self += other;
+        true
+    }
+}
+
+/**
+   Describes what overflowed during a blink
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OverflowKind {
+    /// The stone with this value turned into a stone that doesn't fit into a u128
+    Value(u128),
+    /// The number of stones with this value, or of all stones if None, didn't fit into the count type
+    Count(Option<u128>),
+}
+
+/**
+   Reports the blink at which the stones overflowed. The first blink is iteration 1.
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StoneOverflow {
+    pub iteration: u32,
+    pub kind: OverflowKind,
+}
+
+impl std::fmt::Display for StoneOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            OverflowKind::Value(value) => write!(f, "Stone {} overflowed during iteration {}", value, self.iteration),
+            OverflowKind::Count(Some(value)) => write!(f, "The count of stone {} overflowed during iteration {}", value, self.iteration),
+            OverflowKind::Count(None) => write!(f, "The total stone count overflowed after iteration {}", self.iteration),
+        }
+    }
+}
+
+/**
+   Statistics about the memo table of a `StoneEngine`
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of values currently stored in the memo table
+    pub entries: usize,
+}
+
+/**
+   Applies the blink rules to stones, and remembers the result for every processed stone value.
+   Each engine owns its memo table, so separate runs don't share any state unless they share
+   the engine. The memo table can be bounded, in which case no new values are remembered once
+   the bound is reached. By default the engine uses the puzzle rules.
+ */
+#[derive(Debug)]
+pub struct StoneEngine<R: StoneRules = RuleSet> {
+    rules: R,
+    cache: HashMap<u128, BlinkResult>,
+    capacity: Option<usize>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for StoneEngine {
+    fn default() -> Self {
+        Self::with_rules(RuleSet::default())
+    }
+}
+
+impl StoneEngine {
+    /**
+       Creates an engine with the puzzle rules and an unbounded memo table
+     */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+       Creates an engine with the puzzle rules that remembers at most `capacity` stone values
+     */
+    pub fn with_capacity(capacity: usize) -> Self {
+        StoneEngine { capacity: Some(capacity), ..Self::default() }
+    }
+}
+
+impl<R: StoneRules> StoneEngine<R> {
+    /**
+       Creates an engine with the given rules and an unbounded memo table
+     */
+    pub fn with_rules(rules: R) -> Self {
+        StoneEngine { rules, cache: HashMap::new(), capacity: None, hits: 0, misses: 0 }
+    }
+
+    /**
+       Changes the bound of the memo table. If the table already holds more values than the
+       new bound allows, it is cleared.
+     */
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        if capacity.is_some_and(|capacity| self.cache.len() > capacity) {
+            self.cache.clear();
+        }
+    }
+
+    /**
+       Forgets all remembered values and resets the statistics
+     */
+    pub fn reset(&mut self) {
+        self.cache.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits, misses: self.misses, entries: self.cache.len() }
+    }
+
+    /**
+       Returns the stone(s) the given stone turns into, using the memo table if possible.
+       Returns None if a resulting stone doesn't fit into a u128.
+     */
+    pub fn blink(&mut self, value: u128) -> Option<BlinkResult> {
+        if let Some(result) = self.cache.get(&value) {
+            self.hits += 1;
+            return Some(result.clone());
+        }
+
+        self.misses += 1;
+        let result = self.rules.blink(value)?;
+        if self.capacity.is_none_or(|capacity| self.cache.len() < capacity) {
+            self.cache.insert(value, result.clone());
+        }
+
+        Some(result)
+    }
+
+    /**
+       Steps through each stone in the stones count map and applies the rules. The result is the new
+       stones count map after a full iteration, or what overflowed during the iteration.
+     */
+    pub fn iterate<C: StoneCount>(&mut self, stones_count_map: &HashMap<u128, C>) -> Result<HashMap<u128, C>, OverflowKind> {
+        let mut new_stones_count_map: HashMap<u128, C> = HashMap::new();
+
+        // For each unique stone engraving in the map, compute the resulting stone(s)
+        // Then insert the found stones into the result map with the original count.
+        // If there are five stones with engraving 2 in the original map, the same
+        // stone result will be contained five times after one iteration as well.
+        for (stone, count) in stones_count_map {
+            let new_stones = self.blink(*stone).ok_or(OverflowKind::Value(*stone))?;
+
+            for new_stone in new_stones.stones() {
                 // In case the same result was already contained in the result map,
                 // both counts are just summed up during the update.
-                new_stones_count_map.insert(*new_stone, *c + *count);
-            } else {
-                new_stones_count_map.insert(*new_stone, *count);
+                if let Some(c) = new_stones_count_map.get_mut(&new_stone) {
+                    if !c.checked_add_assign(count) {
+                        return Err(OverflowKind::Count(Some(new_stone)));
+                    }
+                } else {
+                    new_stones_count_map.insert(new_stone, count.clone());
+                }
             }
         }
+
+        Ok(new_stones_count_map)
     }
+}
 
-    new_stones_count_map
+/**
+   Returns the number of stones after the given number of iterations, or the iteration at which
+   either a stone or a count overflowed. The count type decides how large the counts may get,
+   `BigCount` never overflows.
+ */
+pub fn count_stones<C: StoneCount>(path: &str, iterations: u32) -> Result<C, StoneOverflow> {
+    count_stones_with(path, iterations, RuleSet::default())
 }
 
 /**
-   Returns the number of stones after the given number of iterations
+   Same as `count_stones`, but blinks with the given rules instead of the puzzle rules
  */
-pub fn both_parts(path: &str, iterations: u8) -> u128 {
-    let stones = preprocess(path);
-    // Stores the number of occurences for each stone value
-    let mut stones_count_map: HashMap<u128, u128> = HashMap::new();
+pub fn count_stones_with<C: StoneCount, R: StoneRules>(path: &str, iterations: u32, rules: R) -> Result<C, StoneOverflow> {
+    let mut stones_count_map: HashMap<u128, C> = initial_count_map(&preprocess(path))?;
+
+    let mut engine = StoneEngine::with_rules(rules);
+    for iteration in 1..=iterations {
+        stones_count_map = engine.iterate(&stones_count_map)
+            .map_err(|kind| StoneOverflow { iteration, kind })?;
+    }
+
+    total_count(&stones_count_map).ok_or(StoneOverflow { iteration: iterations, kind: OverflowKind::Count(None) })
+}
+
+/**
+   Returns the map that stores the number of occurences for each stone value
+ */
+fn initial_count_map<C: StoneCount>(stones: &[u128]) -> Result<HashMap<u128, C>, StoneOverflow> {
+    let mut stones_count_map: HashMap<u128, C> = HashMap::new();
 
-    for stone in &stones {
-        if let Some(count) = stones_count_map.get(stone) {
-            stones_count_map.insert(*stone, (*count) + 1);
+    for stone in stones {
+        if let Some(count) = stones_count_map.get_mut(stone) {
+            if !count.checked_add_assign(&C::one()) {
+                return Err(StoneOverflow { iteration: 0, kind: OverflowKind::Count(Some(*stone)) });
+            }
         } else {
-            stones_count_map.insert(*stone, 1);
+            stones_count_map.insert(*stone, C::one());
+        }
+    }
+
+    Ok(stones_count_map)
+}
+
+/**
+   Sums up the counts of all stones, or returns None if the sum overflows
+ */
+fn total_count<C: StoneCount>(stones_count_map: &HashMap<u128, C>) -> Option<C> {
+    let mut amount = C::zero();
+    for count in stones_count_map.values() {
+        if !amount.checked_add_assign(count) {
+            return None;
+        }
+    }
+
+    Some(amount)
+}
+
+/**
+   Returns the number of digits of the value in base 10
+ */
+fn digit_count(value: u128) -> usize {
+    value.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/**
+   Describes the stone population after a single iteration
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlinkStats<C = u128> {
+    /// The number of blinks so far, 0 is the initial arrangement
+    pub iteration: u32,
+    /// The number of distinct engravings
+    pub distinct: usize,
+    /// The number of engravings that didn't appear in any earlier iteration
+    pub new_values: usize,
+    pub total: C,
+    pub largest: u128,
+    /// The number of stones for each number of digits, index 0 counts the stones with one digit
+    pub digit_histogram: Vec<C>,
+}
+
+/**
+   Computes the statistics of the given stones count map
+ */
+fn stats_of<C: StoneCount>(iteration: u32, stones_count_map: &HashMap<u128, C>, new_values: usize) -> Result<BlinkStats<C>, StoneOverflow> {
+    let overflow = StoneOverflow { iteration, kind: OverflowKind::Count(None) };
+    let mut digit_histogram: Vec<C> = vec![];
+
+    for (stone, count) in stones_count_map {
+        let digits = digit_count(*stone);
+        if digit_histogram.len() < digits {
+            digit_histogram.resize(digits, C::zero());
+        }
+
+        if !digit_histogram[digits - 1].checked_add_assign(count) {
+            return Err(overflow);
+        }
+    }
+
+    Ok(BlinkStats {
+        iteration,
+        distinct: stones_count_map.len(),
+        new_values,
+        total: total_count(stones_count_map).ok_or(overflow)?,
+        largest: stones_count_map.keys().max().copied().unwrap_or(0),
+        digit_histogram,
+    })
+}
+
+/**
+   Returns the statistics of the stone population for the initial arrangement and after every
+   single one of the given number of iterations with the given rules
+ */
+pub fn blink_statistics<C: StoneCount, R: StoneRules>(path: &str, iterations: u32, rules: R) -> Result<Vec<BlinkStats<C>>, StoneOverflow> {
+    let mut stones_count_map: HashMap<u128, C> = initial_count_map(&preprocess(path))?;
+    let mut seen: HashSet<u128> = stones_count_map.keys().copied().collect();
+    let mut stats = vec![stats_of(0, &stones_count_map, seen.len())?];
+
+    let mut engine = StoneEngine::with_rules(rules);
+    for iteration in 1..=iterations {
+        stones_count_map = engine.iterate(&stones_count_map)
+            .map_err(|kind| StoneOverflow { iteration, kind })?;
+
+        let new_values = stones_count_map.keys().filter(|stone| seen.insert(**stone)).count();
+        stats.push(stats_of(iteration, &stones_count_map, new_values)?);
+    }
+
+    Ok(stats)
+}
+
+/**
+   Returns the first iteration that didn't produce any new engraving. From that iteration on,
+   the set of all engravings seen so far is closed: every stone in it only ever turns into
+   stones that are in it as well, so the number of distinct engravings can't grow anymore.
+   Returns None if the statistics don't reach that point.
+ */
+pub fn closed_orbit_iteration<C>(stats: &[BlinkStats<C>]) -> Option<u32> {
+    stats.iter()
+        .find(|stats| stats.iteration > 0 && stats.new_values == 0)
+        .map(|stats| stats.iteration)
+}
+
+/**
+   Turns the statistics into CSV with a header line, one line per iteration. The histogram is
+   spread over the columns `digits_1` to `digits_n`, where n is the longest engraving seen.
+ */
+pub fn statistics_to_csv<C: StoneCount + std::fmt::Display>(stats: &[BlinkStats<C>]) -> String {
+    let max_digits = stats.iter().map(|stats| stats.digit_histogram.len()).max().unwrap_or(0);
+    let mut header: Vec<String> = ["iteration", "distinct", "new_values", "total", "largest"].iter()
+        .map(|column| column.to_string())
+        .collect();
+    header.extend((1..=max_digits).map(|digits| format!("digits_{}", digits)));
+
+    let mut csv = header.join(",") + "\n";
+
+    for stats in stats {
+        let mut columns: Vec<String> = vec![
+            stats.iteration.to_string(),
+            stats.distinct.to_string(),
+            stats.new_values.to_string(),
+            stats.total.to_string(),
+            stats.largest.to_string(),
+        ];
+        columns.extend((0..max_digits).map(|index| {
+            stats.digit_histogram.get(index).map_or("0".to_string(), |count| count.to_string())
+        }));
+
+        csv.push_str(&columns.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/**
+   The blink rules as a linear map on stone counts. Once all engravings that can ever appear form
+   a closed set, a blink just moves the count of each engraving to the engravings it turns into.
+   Row i of the sparse matrix lists the engravings that engraving i turns into.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionMatrix {
+    /// The engravings of the closed set in ascending order
+    pub values: Vec<u128>,
+    /// The indices of the engravings each engraving turns into. An index is listed twice if a
+    /// stone splits into two equal stones.
+    pub successors: Vec<Vec<usize>>,
+}
+
+impl TransitionMatrix {
+    /**
+       Discovers the closed set of engravings that can be reached from the given stones, and
+       builds the transition matrix for it. Returns None if the set grows beyond `limit`
+       engravings, or if a stone overflows.
+     */
+    pub fn discover<R: StoneRules>(stones: &[u128], rules: &R, limit: usize) -> Option<Self> {
+        let mut results: HashMap<u128, BlinkResult> = HashMap::new();
+        let mut queue: Vec<u128> = stones.to_vec();
+
+        while let Some(stone) = queue.pop() {
+            if results.contains_key(&stone) {
+                continue;
+            }
+            if results.len() == limit {
+                return None;
+            }
+
+            let result = rules.blink(stone)?;
+            queue.extend(result.stones().filter(|new_stone| !results.contains_key(new_stone)));
+            results.insert(stone, result);
+        }
+
+        let mut values: Vec<u128> = results.keys().copied().collect();
+        values.sort();
+        let index: HashMap<u128, usize> = values.iter().enumerate().map(|(i, value)| (*value, i)).collect();
+        let successors = values.iter()
+            .map(|value| results[value].stones().map(|stone| index[&stone]).collect())
+            .collect();
+
+        Some(TransitionMatrix { values, successors })
+    }
+
+    /**
+       Returns the index of the given engraving in the closed set
+     */
+    pub fn index_of(&self, value: u128) -> Option<usize> {
+        self.values.binary_search(&value).ok()
+    }
+
+    /**
+       Returns the count vector of the given stones modulo the modulus. Panics if a stone isn't
+       part of the closed set.
+     */
+    pub fn counts_of(&self, stones: &[u128], modulus: u64) -> Vec<u64> {
+        let mut counts = vec![0; self.values.len()];
+        for stone in stones {
+            let index = self.index_of(*stone).expect("Stone should be part of the closed set");
+            counts[index] = (counts[index] + 1) % modulus;
+        }
+
+        counts
+    }
+
+    /**
+       Applies a single blink to the count vector, modulo the modulus
+     */
+    pub fn apply(&self, counts: &[u64], modulus: u64) -> Vec<u64> {
+        let mut new_counts = vec![0; counts.len()];
+
+        for (count, successors) in counts.iter().zip(&self.successors) {
+            for successor in successors {
+                new_counts[*successor] = add_mod(new_counts[*successor], *count, modulus);
+            }
+        }
+
+        new_counts
+    }
+
+    /**
+       Returns the count vector after the given number of blinks modulo the modulus, starting
+       with the given count vector. Returns None if the modulus isn't prime.
+
+       Instead of stepping one blink at a time, this raises the matrix to the power of the
+       iterations. A dense power of a matrix with thousands of rows would be far too slow, so the
+       power is taken in the algebra of the matrix instead: the count vectors after n blinks
+       follow a linear recurrence whose order is at most the size of the matrix. It is found with
+       the Berlekamp-Massey algorithm from a random projection of the vectors, which needs a
+       prime modulus, and then verified on the vectors themselves. x^n is computed modulo the
+       characteristic polynomial of that recurrence by repeated squaring, which is exponentiation
+       of its companion matrix, and the result combines the first vectors.
+     */
+    pub fn counts_after(&self, counts: &[u64], iterations: u64, modulus: u64) -> Option<Vec<u64>> {
+        if !is_prime(modulus) {
+            return None;
+        }
+
+        // The recurrence is guaranteed to be found from twice as many terms as its order
+        let terms = 2 * self.values.len() + 2;
+        if iterations < terms as u64 {
+            return Some((0..iterations).fold(counts.to_vec(), |current, _| self.apply(&current, modulus)));
+        }
+
+        // A xorshift generator for the projections, so that results are reproducible
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % modulus
+        };
+
+        // A projection only misses part of the recurrence with a probability of about order /
+        // modulus, so more attempts are only needed for tiny moduli
+        for _ in 0..64 {
+            let weights: Vec<u64> = (0..counts.len()).map(|_| random()).collect();
+            let project = |vector: &[u64]| vector.iter().zip(&weights)
+                .fold(0, |sum, (count, weight)| add_mod(sum, mul_mod(*count, *weight, modulus), modulus));
+
+            let mut projections: Vec<u64> = Vec::with_capacity(terms);
+            let mut current = counts.to_vec();
+            for _ in 0..terms {
+                projections.push(project(&current));
+                current = self.apply(&current, modulus);
+            }
+
+            let recurrence = berlekamp_massey(&projections, modulus);
+            if let Some(result) = self.combine(counts, &recurrence, iterations, modulus) {
+                return Some(result);
+            }
+        }
+
+        panic!("No linear recurrence found for the count vectors modulo {}", modulus);
+    }
+
+    /**
+       Returns the count vector after the given number of blinks as the combination of the first
+       count vectors, given by x^iterations modulo the characteristic polynomial of the
+       recurrence. Returns None if the count vectors don't follow the recurrence.
+     */
+    fn combine(&self, counts: &[u64], recurrence: &[u64], iterations: u64, modulus: u64) -> Option<Vec<u64>> {
+        let order = recurrence.len();
+        let coefficients = power_of_x_modulo(recurrence, iterations, modulus);
+        let mut result = vec![0; counts.len()];
+        // What the recurrence predicts for the count vector after `order` blinks
+        let mut predicted = vec![0; counts.len()];
+        let mut current = counts.to_vec();
+
+        for i in 0..order {
+            let (coefficient, weight) = (coefficients[i], recurrence[order - 1 - i]);
+            for (j, count) in current.iter().enumerate() {
+                result[j] = add_mod(result[j], mul_mod(coefficient, *count, modulus), modulus);
+                predicted[j] = add_mod(predicted[j], mul_mod(weight, *count, modulus), modulus);
+            }
+            current = self.apply(&current, modulus);
+        }
+
+        (current == predicted).then_some(result)
+    }
+
+    /**
+       Returns the total number of stones after the given number of blinks modulo the modulus,
+       starting with the given count vector. Returns None if the modulus isn't prime.
+     */
+    pub fn total_after(&self, counts: &[u64], iterations: u64, modulus: u64) -> Option<u64> {
+        let counts = self.counts_after(counts, iterations, modulus)?;
+        Some(counts.iter().fold(0, |sum, count| add_mod(sum, *count, modulus)))
+    }
+}
+
+fn add_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128 + b as u128) % modulus as u128) as u64
+}
+
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128 * b as u128) % modulus as u128) as u64
+}
+
+/**
+   Decides whether the number is prime with the Miller-Rabin test. These bases are enough for
+   the test to be exact for all u64.
+ */
+pub fn is_prime(number: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if number < 2 {
+        return false;
+    }
+    if let Some(base) = BASES.iter().find(|base| number.is_multiple_of(**base)) {
+        return number == *base;
+    }
+
+    let odd_part = (number - 1) >> (number - 1).trailing_zeros();
+    BASES.iter().all(|base| {
+        let mut x = pow_mod(*base, odd_part, number);
+        let mut exponent = odd_part;
+        if x == 1 {
+            return true;
+        }
+        while exponent < number - 1 {
+            if x == number - 1 {
+                return true;
+            }
+            x = mul_mod(x, x, number);
+            exponent *= 2;
+        }
+        false
+    })
+}
+
+/**
+   Returns base^exponent modulo the modulus
+ */
+fn pow_mod(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    let mut result = 1 % modulus;
+    base %= modulus;
+
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = ((result as u128 * base as u128) % modulus as u128) as u64;
         }
+        base = ((base as u128 * base as u128) % modulus as u128) as u64;
+        exponent >>= 1;
     }
 
-    for _ in 0..iterations {
-        stones_count_map = iterate(&stones_count_map);
+    result
+}
+
+/**
+   Finds the shortest linear recurrence s[n] = c[0] * s[n-1] + ... + c[l-1] * s[n-l] that generates
+   the sequence modulo the prime modulus, and returns its coefficients c.
+ */
+fn berlekamp_massey(sequence: &[u64], modulus: u64) -> Vec<u64> {
+    let mul = |a: u64, b: u64| mul_mod(a, b, modulus);
+    let n = sequence.len();
+    // The connection polynomials 1 + c[1] * x + ... of the current and the last changed recurrence
+    let mut current: Vec<u64> = vec![0; n + 1];
+    let mut last: Vec<u64> = vec![0; n + 1];
+    current[0] = 1;
+    last[0] = 1;
+
+    let mut length = 0;
+    let mut shift = 0;
+    let mut last_discrepancy = 1;
+
+    for i in 0..n {
+        shift += 1;
+
+        // The difference between the sequence and what the current recurrence predicts
+        let discrepancy = (1..=length).fold(sequence[i] % modulus, |sum, j| add_mod(sum, mul(current[j], sequence[i - j]), modulus));
+        if discrepancy == 0 {
+            continue;
+        }
+
+        let previous = current.clone();
+        let factor = mul(discrepancy, pow_mod(last_discrepancy, modulus - 2, modulus));
+        for j in shift..=n {
+            current[j] = add_mod(current[j], modulus - mul(factor, last[j - shift]), modulus);
+        }
+
+        if 2 * length > i {
+            continue;
+        }
+
+        length = i + 1 - length;
+        last = previous;
+        last_discrepancy = discrepancy;
+        shift = 0;
+    }
+
+    current[1..=length].iter().map(|c| (modulus - c) % modulus).collect()
+}
+
+/**
+   Returns the coefficients of x^exponent modulo the characteristic polynomial
+   x^l - c[0] * x^(l-1) - ... - c[l-1] of the given recurrence. The n-th term of the sequence
+   is the sum of these coefficients multiplied with the first l terms.
+ */
+fn power_of_x_modulo(recurrence: &[u64], exponent: u64, modulus: u64) -> Vec<u64> {
+    let order = recurrence.len();
+    if order == 0 {
+        return vec![];
+    }
+
+    let multiply = |a: &[u64], b: &[u64]| -> Vec<u64> {
+        let mut product = vec![0u128; 2 * order - 1];
+        for (i, x) in a.iter().enumerate() {
+            if *x == 0 {
+                continue;
+            }
+            for (j, y) in b.iter().enumerate() {
+                product[i + j] = (product[i + j] + *x as u128 * *y as u128) % modulus as u128;
+            }
+        }
+
+        // Replace x^k with c[0] * x^(k-1) + ... + c[l-1] * x^(k-l), from the highest power down
+        for k in (order..2 * order - 1).rev() {
+            let coefficient = product[k];
+            if coefficient == 0 {
+                continue;
+            }
+            for (i, c) in recurrence.iter().enumerate() {
+                product[k - i - 1] = (product[k - i - 1] + coefficient * *c as u128) % modulus as u128;
+            }
+        }
+
+        product.into_iter().take(order).map(|coefficient| coefficient as u64).collect()
+    };
+
+    // Start with x^0, and x itself is x^1 unless the polynomial only has degree 1
+    let mut result = vec![0; order];
+    result[0] = 1 % modulus;
+    let mut base = vec![0; order];
+    if order > 1 {
+        base[1] = 1;
+    } else {
+        base[0] = recurrence[0] % modulus;
+    }
+
+    let mut exponent = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = multiply(&result, &base);
+        }
+        base = multiply(&base, &base);
+        exponent >>= 1;
+    }
+
+    result
+}
+
+/**
+   The reasons why the number of stones can't be computed modulo a number
+ */
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ModuloError {
+    /// The modulus isn't prime
+    NotPrime(u64),
+    /// The engravings reachable from the input don't form a closed set of at most this many
+    /// values, or a stone overflowed while discovering them
+    NotClosed(usize),
+}
+
+impl std::fmt::Display for ModuloError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModuloError::NotPrime(modulus) => write!(f, "The modulus {} isn't prime", modulus),
+            ModuloError::NotClosed(limit) => write!(f, "The engravings don't form a closed set of at most {} values", limit),
+        }
     }
+}
+
+impl std::error::Error for ModuloError {}
+
+/**
+   Returns the number of stones after the given number of iterations modulo the given prime,
+   using the transition matrix of the closed set of engravings. This works for iteration
+   numbers far beyond what stepping through the blinks could ever reach. The closed set may
+   have at most `limit` engravings, since the matrix has to be built for all of them.
+ */
+pub fn count_stones_modulo<R: StoneRules>(path: &str, iterations: u64, modulus: u64, rules: R, limit: usize) -> Result<u64, ModuloError> {
+    if !is_prime(modulus) {
+        return Err(ModuloError::NotPrime(modulus));
+    }
+
+    let stones = preprocess(path);
+    let matrix = TransitionMatrix::discover(&stones, &rules, limit).ok_or(ModuloError::NotClosed(limit))?;
+    let counts = matrix.counts_of(&stones, modulus);
+
+    matrix.total_after(&counts, iterations, modulus).ok_or(ModuloError::NotPrime(modulus))
+}
 
-    let amount = stones_count_map.values().sum();
+/**
+   Returns the number of stones after the given number of iterations. Panics with the iteration
+   that overflowed if the number doesn't fit into a u128.
+ */
+pub fn both_parts(path: &str, iterations: u32) -> u128 {
+    let amount: u128 = count_stones(path, iterations).unwrap_or_else(|overflow| panic!("{}", overflow));
+    println!("After {} iterations there are {} stones", iterations, amount);
+    amount
+}
+
+/**
+   Returns the exact number of stones after the given number of iterations, no matter how large
+ */
+pub fn both_parts_exact(path: &str, iterations: u32) -> BigCount {
+    let amount: BigCount = count_stones(path, iterations).unwrap_or_else(|overflow| panic!("{}", overflow));
     println!("After {} iterations there are {} stones", iterations, amount);
     amount
 }
@@ -112,10 +1016,12 @@ mod test {
 
     #[test]
     fn test_process_stone() {
-        assert_eq!(process_stone(0), vec![1]);
-        assert_eq!(process_stone(23), vec![2, 3]);
-        assert_eq!(process_stone(2), vec![4048]);
-        assert_eq!(process_stone(4001), vec![40, 1]);
+        assert_eq!(RuleSet::default().blink(0), Some(BlinkResult::One(1)));
+        assert_eq!(RuleSet::default().blink(23), Some(BlinkResult::Two(2, 3)));
+        assert_eq!(RuleSet::default().blink(2), Some(BlinkResult::One(4048)));
+        assert_eq!(RuleSet::default().blink(4001), Some(BlinkResult::Two(40, 1)));
+        assert_eq!(RuleSet::default().blink(4001).unwrap().stones().collect::<Vec<u128>>(), vec![40, 1]);
+        assert_eq!(RuleSet::default().blink(u128::MAX), None);
     }
 
     #[test]
@@ -131,11 +1037,283 @@ mod test {
         map.insert(23, 1);
         map.insert(99, 1);
         map.insert(2, 1);
-        let result = iterate(&map);
+        let result = StoneEngine::new().iterate(&map).unwrap();
         let mut keys: Vec<u128> = result.keys().map(|k| *k).collect();
         keys.sort();
 
         assert_eq!(keys, vec![1, 2, 3, 9, 4048]);
         assert_eq!(*result.get(&9).unwrap(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_stone_engine_stats() {
+        let mut engine = StoneEngine::new();
+        let mut map: HashMap<u128, u128> = HashMap::new();
+        map.insert(0, 1);
+        map.insert(1, 1);
+
+        // 0 -> 1 and 1 -> 2024, the second blink on 1 is a hit
+        let map = engine.iterate(&map).unwrap();
+        let _ = engine.iterate(&map).unwrap();
+        assert_eq!(engine.stats(), CacheStats { hits: 1, misses: 3, entries: 3 });
+
+        engine.reset();
+        assert_eq!(engine.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn test_stone_engine_capacity() {
+        let mut engine = StoneEngine::with_capacity(1);
+        engine.blink(0);
+        engine.blink(1);
+        engine.blink(1);
+        assert_eq!(engine.stats(), CacheStats { hits: 0, misses: 3, entries: 1 });
+
+        engine.set_capacity(Some(0));
+        assert_eq!(engine.stats().entries, 0);
+        engine.set_capacity(None);
+        engine.blink(1);
+        engine.blink(1);
+        assert_eq!(engine.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_big_count() {
+        let mut count = BigCount::from(u128::MAX);
+        count += &BigCount::from(1);
+        assert_eq!(count.to_u128(), None);
+        assert_eq!(count.to_string(), "340282366920938463463374607431768211456");
+
+        count += &count.clone();
+        assert_eq!(count.to_string(), "680564733841876926926749214863536422912");
+        assert_eq!(BigCount::from(0).to_string(), "0");
+        assert_eq!(BigCount::from(12345).to_u128(), Some(12345));
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        let mut map: HashMap<u128, u128> = HashMap::new();
+        map.insert(10, u128::MAX);
+        map.insert(0, 1);
+        // 10 -> 1, 0 and 0 -> 1 both create stones with engraving 1
+        assert_eq!(StoneEngine::new().iterate(&map), Err(OverflowKind::Count(Some(1))));
+
+        let mut map: HashMap<u128, u128> = HashMap::new();
+        map.insert(u128::MAX / 100, 1);
+        assert_eq!(StoneEngine::new().iterate(&map), Err(OverflowKind::Value(u128::MAX / 100)));
+    }
+
+    #[test]
+    fn test_count_stones_overflow() {
+        let result = count_stones::<u128>("./inputs/day_11.txt", 300);
+        let overflow = result.unwrap_err();
+        assert_eq!(overflow.iteration, 215);
+
+        let exact: BigCount = count_stones("./inputs/day_11.txt", 220).unwrap();
+        assert_eq!(exact.to_u128(), None);
+
+        let exact: BigCount = count_stones("./inputs/day_11.txt", 75).unwrap();
+        assert_eq!(exact.to_u128(), count_stones::<u128>("./inputs/day_11.txt", 75).ok());
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let three_parts = RuleSet { split_parts: 3, ..RuleSet::default() };
+        assert_eq!(three_parts.blink(123456), Some(BlinkResult::Many(Box::new([12, 34, 56]))));
+        assert!(std::mem::size_of::<BlinkResult>() <= 3 * std::mem::size_of::<u128>());
+        assert_eq!(three_parts.blink(1005).unwrap().stones().collect::<Vec<u128>>(), vec![1005 * 2024]);
+        assert_eq!(three_parts.blink(100).unwrap().stones().collect::<Vec<u128>>(), vec![1, 0, 0]);
+
+        // 0b1011 has four binary digits
+        let binary = RuleSet { base: 2, ..RuleSet::default() };
+        assert_eq!(binary.blink(0b1011), Some(BlinkResult::Two(0b10, 0b11)));
+        assert_eq!(binary.blink(0b101), Some(BlinkResult::One(0b101 * 2024)));
+
+        let no_zero_rule = RuleSet { zero_replacement: None, multiplier: 3, ..RuleSet::default() };
+        assert_eq!(no_zero_rule.blink(0), Some(BlinkResult::One(0)));
+        assert_eq!(no_zero_rule.blink(7), Some(BlinkResult::One(21)));
+    }
+
+    struct Doubling;
+
+    impl StoneRules for Doubling {
+        fn blink(&self, value: u128) -> Option<BlinkResult> {
+            Some(BlinkResult::Two(value, value))
+        }
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let result: u128 = count_stones_with("./inputs/day_11.txt", 10, Doubling).unwrap();
+        assert_eq!(result, 8 * 1024);
+
+        let puzzle_rules: u128 = count_stones_with("./inputs/day_11.txt", 25, RuleSet::default()).unwrap();
+        assert_eq!(Some(puzzle_rules), count_stones::<u128>("./inputs/day_11.txt", 25).ok());
+    }
+
+    #[test]
+    fn test_digit_count() {
+        assert_eq!(digit_count(0), 1);
+        assert_eq!(digit_count(9), 1);
+        assert_eq!(digit_count(10), 2);
+        assert_eq!(digit_count(u128::MAX), 39);
+    }
+
+    #[test]
+    fn test_blink_statistics() {
+        let stats: Vec<BlinkStats> = blink_statistics("./inputs/day_11.txt", 2, RuleSet::default()).unwrap();
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].total, 8);
+        assert_eq!(stats[0].largest, 1840437);
+        assert_eq!(stats[0].digit_histogram, vec![2, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(stats[1].total, 11);
+        assert_eq!(stats[1].iteration, 1);
+        // The 2 of the first blink was already engraved initially
+        assert_eq!((stats[1].distinct, stats[1].new_values), (11, 10));
+
+        for stats in &stats {
+            assert_eq!(stats.digit_histogram.iter().sum::<u128>(), stats.total);
+        }
+
+        let rules = RuleSet { split_parts: 3, multiplier: 7, ..RuleSet::default() };
+        let stats: Vec<BlinkStats> = blink_statistics("./inputs/day_11.txt", 10, rules).unwrap();
+        let expected: u128 = count_stones_with("./inputs/day_11.txt", 10, rules).unwrap();
+        assert_eq!(stats[10].total, expected);
+        assert_ne!(stats[10].total, blink_statistics::<u128, _>("./inputs/day_11.txt", 10, RuleSet::default()).unwrap()[10].total);
+
+        // Doubling never creates new engravings, so the orbit is closed after the first blink
+        let stats: Vec<BlinkStats> = blink_statistics("./inputs/day_11.txt", 3, Doubling).unwrap();
+        assert_eq!(stats.iter().map(|stats| stats.total).collect::<Vec<u128>>(), vec![8, 16, 32, 64]);
+        assert_eq!(closed_orbit_iteration(&stats), Some(1));
+    }
+
+    #[test]
+    fn test_closed_orbit_iteration() {
+        let stats: Vec<BlinkStats> = blink_statistics("./inputs/day_11.txt", 200, RuleSet::default()).unwrap();
+        let closed = closed_orbit_iteration(&stats).unwrap();
+
+        assert!(stats[closed as usize..].iter().all(|stats| stats.new_values == 0));
+        assert!(stats[closed as usize - 1].new_values > 0);
+        assert_eq!(closed_orbit_iteration(&stats[..closed as usize]), None);
+    }
+
+    #[test]
+    fn test_statistics_to_csv() {
+        let stats: Vec<BlinkStats<BigCount>> = blink_statistics("./inputs/day_11.txt", 1, RuleSet::default()).unwrap();
+
+        assert_eq!(statistics_to_csv(&stats), concat!(
+            "iteration,distinct,new_values,total,largest,digits_1,digits_2,digits_3,digits_4,digits_5,digits_6,digits_7,digits_8,digits_9,digits_10\n",
+            "0,8,8,8,1840437,2,1,1,1,1,1,1,0,0,0\n",
+            "1,11,10,11,3725044488,3,2,2,1,0,1,0,1,0,1\n",
+        ));
+    }
+
+    #[test]
+    fn test_transition_matrix_discover() {
+        let matrix = TransitionMatrix::discover(&[0], &RuleSet::default(), 1000).unwrap();
+
+        assert_eq!(matrix.values.len(), 54);
+        // 0 -> 1 -> 2024 -> 20, 24
+        let one = matrix.index_of(1).unwrap();
+        assert_eq!(matrix.successors[matrix.index_of(0).unwrap()], vec![one]);
+        assert_eq!(matrix.successors[one], vec![matrix.index_of(2024).unwrap()]);
+        assert_eq!(matrix.successors[matrix.index_of(2024).unwrap()], vec![matrix.index_of(20).unwrap(), matrix.index_of(24).unwrap()]);
+
+        assert_eq!(TransitionMatrix::discover(&[0], &RuleSet::default(), 10), None);
+    }
+
+    #[test]
+    fn test_berlekamp_massey() {
+        let modulus = 1_000_000_007;
+        // Fibonacci numbers follow s[n] = s[n-1] + s[n-2]
+        assert_eq!(berlekamp_massey(&[1, 1, 2, 3, 5, 8, 13, 21], modulus), vec![1, 1]);
+        assert_eq!(power_of_x_modulo(&[1, 1], 10, modulus), vec![34, 55]);
+        assert_eq!(pow_mod(3, 200, 1_000_000_007), 136_318_165);
+    }
+
+    #[test]
+    fn test_total_after() {
+        let modulus = 1_000_000_007;
+        let stones = [0, 17];
+        let matrix = TransitionMatrix::discover(&stones, &RuleSet::default(), 1000).unwrap();
+        let counts = matrix.counts_of(&stones, modulus);
+
+        let mut map: HashMap<u128, BigCount> = initial_count_map(&stones).unwrap();
+        let mut engine = StoneEngine::new();
+        for iteration in 0..=400 {
+            let expected = total_count(&map).unwrap().div_rem_in_place(modulus);
+            assert_eq!(matrix.total_after(&counts, iteration, modulus), Some(expected), "{}", iteration);
+            map = engine.iterate(&map).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_count_stones_modulo() {
+        let modulus = 1_000_000_007;
+        let expected = (count_stones::<u128>("./inputs/day_11.txt", 75).unwrap() % modulus as u128) as u64;
+        assert_eq!(count_stones_modulo("./inputs/day_11.txt", 75, modulus, RuleSet::default(), 100_000), Ok(expected));
+        assert_eq!(count_stones_modulo("./inputs/day_11.txt", 75, 1_000_000_000, RuleSet::default(), 100_000), Err(ModuloError::NotPrime(1_000_000_000)));
+        assert_eq!(count_stones_modulo("./inputs/day_11.txt", 75, modulus, RuleSet::default(), 100), Err(ModuloError::NotClosed(100)));
+    }
+
+    #[test]
+    fn test_count_stones_modulo_not_closed() {
+        for split_parts in [3, 8] {
+            let rules = RuleSet { split_parts, ..RuleSet::default() };
+            let result = count_stones_modulo("./inputs/day_11.txt", 75, 1_000_000_007, rules, 10_000);
+            assert_eq!(result, Err(ModuloError::NotClosed(10_000)), "{}", split_parts);
+        }
+
+        // Doubling never creates new engravings, so the set is closed right away
+        let doubled = count_stones_modulo("./inputs/day_11.txt", 10, 1_000_000_007, Doubling, 8);
+        assert_eq!(doubled, Ok(8 * 1024));
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let primes: Vec<u64> = (0..50).filter(|number| is_prime(*number)).collect();
+        assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]);
+        assert!(is_prime(1_000_000_007));
+        assert!(is_prime(18_446_744_073_709_551_557));
+        assert!(!is_prime(1_000_000_000));
+        // The smallest strong pseudoprime to the bases 2, 3, 5 and 7
+        assert!(!is_prime(3_215_031_751));
+    }
+
+    #[test]
+    fn test_counts_after_recurrence() {
+        // Far beyond the number of terms, so that the recurrence is used. Includes a tiny prime
+        // and one close to 2^64, where sums of two counts overflow a u64.
+        let stones = [125, 17];
+        let matrix = TransitionMatrix::discover(&stones, &RuleSet::default(), 1000).unwrap();
+        let iterations = 3 * matrix.values.len() as u64;
+
+        for modulus in [2, 7, 1_000_000_007, 18_446_744_073_709_551_557] {
+            let counts = matrix.counts_of(&stones, modulus);
+            let expected = (0..iterations).fold(counts.clone(), |current, _| matrix.apply(&current, modulus));
+
+            assert_eq!(matrix.counts_after(&counts, iterations, modulus), Some(expected.clone()), "{}", modulus);
+            let total = expected.iter().fold(0, |sum, count| add_mod(sum, *count, modulus));
+            assert_eq!(matrix.total_after(&counts, iterations, modulus), Some(total), "{}", modulus);
+        }
+
+        let counts = matrix.counts_of(&stones, 1);
+        assert_eq!(matrix.counts_after(&counts, iterations, 1), None);
+        assert_eq!(matrix.counts_after(&counts, iterations, 0), None);
+    }
+
+    #[test]
+    fn test_count_stones_modulo_recurrence() {
+        let modulus = 1_000_000_007;
+        let stones = preprocess("./inputs/day_11.txt");
+        let matrix = TransitionMatrix::discover(&stones, &RuleSet::default(), 100_000).unwrap();
+        let iterations = 2 * matrix.values.len() as u64 + 100;
+
+        let counts = matrix.counts_of(&stones, modulus);
+        let expected = (0..iterations).fold(counts, |current, _| matrix.apply(&current, modulus))
+            .iter()
+            .fold(0, |sum, count| add_mod(sum, *count, modulus));
+        assert_eq!(count_stones_modulo("./inputs/day_11.txt", iterations, modulus, RuleSet::default(), 100_000), Ok(expected));
+    }
+}